@@ -1,5 +1,5 @@
-use maven::*;
-use std::path::{PathBuf, Path};
+use maven_rs::*;
+use std::path::{Path, PathBuf};
 
 fn main() {
     let start = std::time::Instant::now();
@@ -17,7 +17,7 @@ fn main() {
             Artifact::pom("androidx.games", "games-activity", "2.0.2"),
         ],
         Path::new("classes"),
-    );
+    ).unwrap();
 
     println!("{:?}", start.elapsed());
 
@@ -26,8 +26,22 @@ fn main() {
     for artifact_fqn in done {
         let path = PathBuf::from("classes/").join(artifact_fqn.filename());
 
-        class_path.push(path.canonicalize().unwrap().display().to_string());
+        match path.canonicalize() {
+            Ok(path) => class_path.push(path.display().to_string()),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
     }
 
     println!("{}", class_path.join(":"));
+
+    // the headers and libraries to link GameActivity with
+    let games_activity = resolver
+        .try_download_package(&Artifact::pom("androidx.games", "games-activity", "2.0.2"))
+        .unwrap();
+    if let Some(prefab) = PrefabPackage::extract(&games_activity, Path::new("prefab")).unwrap() {
+        println!("{:?}", prefab.include_dirs("arm64-v8a", Some("c++_shared")));
+        for instruction in prefab.cargo_link_instructions("arm64-v8a", Some("c++_shared")) {
+            println!("{}", instruction);
+        }
+    }
 }
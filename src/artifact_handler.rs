@@ -0,0 +1,131 @@
+//! What a dependency `<type>` or a project `<packaging>` means: which file to
+//! download and whether it goes on the classpath.
+
+use crate::Artifact;
+use std::collections::HashMap;
+
+/// How the artifacts of a type are stored and used, same as Maven's `ArtifactHandler`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactHandler {
+    /// The dependency type or the packaging, e.g. `test-jar`.
+    pub type_name: String,
+    /// The extension of the file, e.g. `jar` for `test-jar`.
+    pub extension: String,
+    /// The classifier of the file, e.g. `tests` for `test-jar`.
+    pub classifier: Option<String>,
+    pub added_to_classpath: bool,
+}
+
+impl ArtifactHandler {
+    /// A handler for files with the type as their extension, not on the classpath.
+    pub fn new(type_name: &str) -> Self {
+        ArtifactHandler {
+            type_name: type_name.to_owned(),
+            extension: type_name.to_owned(),
+            classifier: None,
+            added_to_classpath: false,
+        }
+    }
+
+    pub fn with_extension(self, extension: &str) -> Self {
+        ArtifactHandler {
+            extension: extension.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn with_classifier(self, classifier: &str) -> Self {
+        ArtifactHandler {
+            classifier: Some(classifier.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_added_to_classpath(self, added_to_classpath: bool) -> Self {
+        ArtifactHandler {
+            added_to_classpath,
+            ..self
+        }
+    }
+
+    /// The file of this type for `id`: the extension, and the classifier unless `id` has one.
+    pub fn artifact(&self, id: &Artifact) -> Artifact {
+        Artifact {
+            packaging: Some(self.extension.clone()),
+            classifier: id.classifier.clone().or_else(|| self.classifier.clone()),
+            ..id.clone()
+        }
+    }
+}
+
+/// The artifact handlers by type, seeded with Maven's defaults plus `bundle` and `aar`.
+///
+/// Types without a handler, e.g. `klib`, are files with the type as their
+/// extension, not on the classpath.
+#[derive(Debug, Clone)]
+pub struct ArtifactHandlers {
+    handlers: HashMap<String, ArtifactHandler>,
+}
+
+impl Default for ArtifactHandlers {
+    fn default() -> Self {
+        let jar = |type_name| {
+            ArtifactHandler::new(type_name)
+                .with_extension("jar")
+                .with_added_to_classpath(true)
+        };
+
+        [
+            ArtifactHandler::new("pom"),
+            jar("jar"),
+            jar("test-jar").with_classifier("tests"),
+            jar("maven-plugin"),
+            jar("ejb"),
+            jar("ejb-client").with_classifier("client"),
+            jar("java-source")
+                .with_classifier("sources")
+                .with_added_to_classpath(false),
+            jar("javadoc").with_classifier("javadoc"),
+            ArtifactHandler::new("war"),
+            ArtifactHandler::new("ear"),
+            ArtifactHandler::new("rar"),
+            // the Felix bundle plugin
+            jar("bundle"),
+            // Android libraries
+            ArtifactHandler::new("aar").with_added_to_classpath(true),
+        ]
+        .into_iter()
+        .fold(Self::empty(), Self::with_handler)
+    }
+}
+
+impl ArtifactHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No handlers at all, not even Maven's.
+    pub fn empty() -> Self {
+        ArtifactHandlers {
+            handlers: HashMap::new(),
+        }
+    }
+
+    pub fn with_handler(mut self, handler: ArtifactHandler) -> Self {
+        self.register(handler);
+        self
+    }
+
+    /// Adds a handler, replacing the one of the same type.
+    pub fn register(&mut self, handler: ArtifactHandler) {
+        self.handlers.insert(handler.type_name.clone(), handler);
+    }
+
+    /// The handler of `type_name`, or the default one for unknown types.
+    pub fn get(&self, type_name: &str) -> ArtifactHandler {
+        self.handlers
+            .get(type_name)
+            .cloned()
+            .unwrap_or_else(|| ArtifactHandler::new(type_name))
+    }
+}
@@ -0,0 +1,770 @@
+//! Async API for tokio-based applications.
+//!
+//! [`AsyncResolver`] shares the configuration and the caches of a [`Resolver`], but
+//! all its network requests go through an [`AsyncUrlFetcher`] and are awaited, so it
+//! works on any runtime, `current_thread` included. Only the JARs are extracted
+//! on tokio's blocking thread pool, the other local files are small enough to be
+//! read and written as they are needed.
+
+use crate::checksum::ChecksumAlgorithm;
+use crate::*;
+use parking_lot::Mutex;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The chunks of the data of a response, see [`AsyncUrlFetcher::send_streaming`].
+pub type Body = tokio::sync::mpsc::Receiver<Result<bytes::Bytes, ResolverError>>;
+
+/// The async counterpart of [`UrlFetcher`].
+pub trait AsyncUrlFetcher: Send + Sync {
+    fn send<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, ResolverError>>;
+
+    /// Same as [`AsyncUrlFetcher::send`], but the data is sent to the returned [`Body`]
+    /// as it arrives, so that large files can be written to disk as they are downloaded.
+    ///
+    /// Fetchers that don't override it buffer the data.
+    fn send_streaming<'a>(
+        &'a self,
+        request: &'a Request,
+    ) -> BoxFuture<'a, Result<(Response, Body), ResolverError>> {
+        Box::pin(async move {
+            let mut response = self.send(request).await?;
+            let (sender, body) = tokio::sync::mpsc::channel(1);
+            let _ = sender.try_send(Ok(std::mem::take(&mut response.data)));
+            Ok((response, body))
+        })
+    }
+
+    /// Checks whether a file exists without downloading it, i.e. sends a `HEAD` request.
+    /// Fetchers that don't override it send a `GET` request and drop the data.
+    fn head<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, ResolverError>> {
+        self.send(request)
+    }
+}
+
+/// The default [`AsyncUrlFetcher`], based on `reqwest`.
+///
+/// reqwest only has timeouts per client, so a client is created for the timeouts of
+/// each repository, unless the fetcher was created with [`ReqwestUrlFetcher::new`].
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestUrlFetcher {
+    client: Option<reqwest::Client>,
+    /// The clients by connect and read timeouts.
+    clients: Arc<Mutex<HashMap<Timeouts, reqwest::Client>>>,
+}
+
+type Timeouts = (Option<Duration>, Option<Duration>);
+
+impl ReqwestUrlFetcher {
+    /// Sends all the requests with `client`, the timeouts of the repositories don't apply.
+    pub fn new(client: reqwest::Client) -> Self {
+        ReqwestUrlFetcher {
+            client: Some(client),
+            clients: Default::default(),
+        }
+    }
+
+    fn client(&self, request: &Request) -> Result<reqwest::Client, ResolverError> {
+        if let Some(client) = &self.client {
+            return Ok(client.clone());
+        }
+
+        let timeouts = (request.connect_timeout, request.read_timeout);
+        let mut clients = self.clients.lock();
+        if let Some(client) = clients.get(&timeouts) {
+            return Ok(client.clone());
+        }
+
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = request.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = request.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| ResolverError::invalid_data(&format!("can't create the HTTP client: {}", e)))?;
+        clients.insert(timeouts, client.clone());
+        Ok(client)
+    }
+
+    /// Sends the request and returns the response without its data.
+    async fn call(
+        &self,
+        method: reqwest::Method,
+        request: &Request,
+    ) -> Result<(Response, reqwest::Response), ResolverError> {
+        let mut builder = self.client(request)?.request(method, &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| transport_error(&request.url, e))?;
+
+        let status = response.status().as_u16();
+        match status {
+            408 | 425 | 429 | 500 | 502 | 503 | 504 => {
+                return Err(ResolverError::transient(&request.url, format!("HTTP {}", status)))
+            }
+            401 | 403 | 407 => return Err(ResolverError::unauthorized(&request.url, status)),
+            400.. => {
+                debug!("{} returned {}", credentials::redact(&request.url), status);
+                return Err(ResolverError::file_not_found(&request.url));
+            }
+            _ => {}
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let validators = Validators {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        };
+
+        let content_range = header(reqwest::header::CONTENT_RANGE);
+
+        Ok((
+            Response {
+                status,
+                data: bytes::Bytes::new(),
+                validators,
+                content_range,
+            },
+            response,
+        ))
+    }
+}
+
+/// Sorts out the failures to send a request, from the ones worth another try.
+fn transport_error(url: &str, e: reqwest::Error) -> ResolverError {
+    // the host name doesn't resolve, the resolver's error is somewhere in the chain
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        if cause.to_string().contains("dns error") {
+            return ResolverError::network(url, "can't resolve the host name");
+        }
+        source = cause.source();
+    }
+
+    match () {
+        _ if e.is_builder() => ResolverError::network(url, "invalid request"),
+        _ if e.is_redirect() => ResolverError::network(url, "too many redirects"),
+        _ => transient(url, e),
+    }
+}
+
+// the errors may contain the URL, so only their kinds are reported
+fn transient(url: &str, e: reqwest::Error) -> ResolverError {
+    let cause = match () {
+        _ if e.is_timeout() => "timed out",
+        _ if e.is_connect() => "can't connect",
+        _ => "connection failed",
+    };
+    ResolverError::transient(url, cause)
+}
+
+impl AsyncUrlFetcher for ReqwestUrlFetcher {
+    fn send<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, ResolverError>> {
+        Box::pin(async move {
+            let (mut response, data) = self.call(reqwest::Method::GET, request).await?;
+            // a connection dropped in the middle of the body is worth another try
+            response.data = data.bytes().await.map_err(|e| transient(&request.url, e))?;
+            Ok(response)
+        })
+    }
+
+    fn send_streaming<'a>(
+        &'a self,
+        request: &'a Request,
+    ) -> BoxFuture<'a, Result<(Response, Body), ResolverError>> {
+        Box::pin(async move {
+            let (response, mut data) = self.call(reqwest::Method::GET, request).await?;
+            let (sender, body) = tokio::sync::mpsc::channel(16);
+            let url = request.url.clone();
+            // stops as soon as the body is dropped
+            tokio::spawn(async move {
+                loop {
+                    let chunk = match data.chunk().await {
+                        Ok(Some(chunk)) => Ok(chunk),
+                        Ok(None) => break,
+                        Err(e) => Err(transient(&url, e)),
+                    };
+                    let failed = chunk.is_err();
+                    if sender.send(chunk).await.is_err() || failed {
+                        break;
+                    }
+                }
+            });
+            Ok((response, body))
+        })
+    }
+
+    fn head<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, ResolverError>> {
+        Box::pin(async move {
+            let (response, _) = self.call(reqwest::Method::HEAD, request).await?;
+            Ok(response)
+        })
+    }
+}
+
+/// Sends a request with an [`AsyncUrlFetcher`], e.g. `|url_fetcher, request| url_fetcher.head(request)`.
+type Fetch = for<'r> fn(&'r dyn AsyncUrlFetcher, &'r Request) -> BoxFuture<'r, Result<Response, ResolverError>>;
+
+/// Same as [`RepositoryFetcher`] for an [`AsyncUrlFetcher`]: sends the repository's
+/// credentials along with every request, applies its timeouts and retries transient failures.
+struct AsyncRepositoryFetcher<'a> {
+    url_fetcher: &'a dyn AsyncUrlFetcher,
+    repository: RepositoryFetcher<'a>,
+}
+
+impl AsyncRepositoryFetcher<'_> {
+    async fn with_retries(&self, request: &Request, fetch: Fetch) -> Result<Response, ResolverError> {
+        let request = self.repository.prepare(request);
+        let fetch_policy = &self.repository.fetch_policy;
+
+        let mut retry = 0;
+        loop {
+            match fetch(self.url_fetcher, &request).await {
+                Err(e) if e.is_transient() && retry < fetch_policy.max_retries => {
+                    let backoff = fetch_policy.backoff(retry);
+                    warn!("{}, retrying in {:?}", e.msg, backoff);
+                    tokio::time::sleep(backoff).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The first checksum file next to `url`: its algorithm and, if it can be parsed, the checksum.
+    async fn checksum(&self, url: &str) -> Result<Option<(ChecksumAlgorithm, Option<String>)>, ResolverError> {
+        for algorithm in ChecksumAlgorithm::ALL {
+            let checksum_url = format!("{}.{}", url, algorithm.extension());
+            match self.send(&Request::new(&checksum_url)).await {
+                Ok(response) => {
+                    if let Ok(text) = std::str::from_utf8(&response.data) {
+                        return Ok(Some((algorithm, checksum::parse_checksum_file(algorithm, text))));
+                    }
+                }
+                // the checksum file may exist, don't take the file for unchecked
+                Err(e) if e.is_unreachable() => return Err(e),
+                Err(_) => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl AsyncUrlFetcher for AsyncRepositoryFetcher<'_> {
+    fn send<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, ResolverError>> {
+        Box::pin(self.with_retries(request, |url_fetcher, request| url_fetcher.send(request)))
+    }
+
+    // not retried here, the download resumes where the failed attempt stopped
+    fn send_streaming<'a>(
+        &'a self,
+        request: &'a Request,
+    ) -> BoxFuture<'a, Result<(Response, Body), ResolverError>> {
+        Box::pin(async move { self.url_fetcher.send_streaming(&self.repository.prepare(request)).await })
+    }
+
+    fn head<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Result<Response, ResolverError>> {
+        Box::pin(self.with_retries(request, |url_fetcher, request| url_fetcher.head(request)))
+    }
+}
+
+type Locks = Mutex<HashMap<Artifact, Arc<tokio::sync::Mutex<()>>>>;
+
+/// Same as [`ArtifactLock`], waited for asynchronously.
+struct AsyncArtifactLock<'a> {
+    locks: &'a Locks,
+    id: Artifact,
+    guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+}
+
+impl Drop for AsyncArtifactLock<'_> {
+    fn drop(&mut self) {
+        let mut locks = self.locks.lock();
+        self.guard.take();
+        if locks.get(&self.id).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            locks.remove(&self.id);
+        }
+    }
+}
+
+/// The async version of [`Resolver`].
+///
+/// Cloning is cheap, all the clones share the same caches.
+#[derive(Clone)]
+pub struct AsyncResolver {
+    resolver: Arc<Resolver>,
+    url_fetcher: Arc<dyn AsyncUrlFetcher>,
+    /// Same as [`Resolver::locks`], for the resolutions of this resolver.
+    locks: Arc<Locks>,
+}
+
+impl AsyncResolver {
+    /// Uses the configuration and the caches of `resolver`, and fetches everything with
+    /// [`ReqwestUrlFetcher`].
+    pub fn new(resolver: Resolver) -> Self {
+        Self::with_url_fetcher(resolver, ReqwestUrlFetcher::default())
+    }
+
+    /// Same as [`AsyncResolver::new`] with a custom fetcher.
+    pub fn with_url_fetcher(resolver: Resolver, url_fetcher: impl AsyncUrlFetcher + 'static) -> Self {
+        AsyncResolver {
+            resolver: Arc::new(resolver),
+            url_fetcher: Arc::new(url_fetcher),
+            locks: Default::default(),
+        }
+    }
+
+    /// The underlying resolver, e.g. to inspect its caches.
+    ///
+    /// Resolving with it blocks the thread and fetches with its own [`UrlFetcher`].
+    /// Its downloads and the downloads of the async resolver don't wait for
+    /// each other, so don't resolve the same artifacts with both at the same time.
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    fn fetcher_for(&self, repository: &Repository) -> AsyncRepositoryFetcher<'_> {
+        AsyncRepositoryFetcher {
+            url_fetcher: self.url_fetcher.as_ref(),
+            repository: self.resolver.fetcher_for(repository),
+        }
+    }
+
+    /// Same as [`Resolver::fetch_remote`].
+    async fn fetch_remote(
+        &self,
+        repository: &Repository,
+        id: &Artifact,
+        request: impl FnOnce(&str) -> Request + Send,
+        fetch: Fetch,
+    ) -> Result<Response, ResolverError> {
+        let url = self.resolver.remote_url(repository, id)?;
+        fetch(&self.fetcher_for(repository), &request(&url))
+            .await
+            .inspect_err(|e| self.resolver.note_missing(repository, id, e))
+    }
+
+    /// Same as [`Resolver::lock`].
+    async fn lock(&self, id: &Artifact) -> AsyncArtifactLock<'_> {
+        let lock = self.locks.lock().entry(id.clone()).or_default().clone();
+        AsyncArtifactLock {
+            locks: &self.locks,
+            id: id.clone(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+
+    /// Same as [`Resolver::download`].
+    async fn download(&self, repository: &Repository, id: &Artifact) -> Result<PathBuf, ResolverError> {
+        let _downloading = self.lock(id).await;
+        let resolver = &self.resolver;
+
+        let (path, validators) = match resolver.download_target(repository, id)? {
+            DownloadTarget::Found(path) => return Ok(path),
+            DownloadTarget::Download { path, validators } => (path, validators),
+        };
+        let part = download::part_path(&path, &repository.id);
+
+        let url = resolver.remote_url(repository, id)?;
+        let url_fetcher = self.fetcher_for(repository);
+        let fetch_policy = &repository.fetch_policy;
+        let fetched = download::download_async(&url_fetcher, fetch_policy, &url, &part, validators.as_ref())
+            .await
+            .inspect_err(|e| resolver.note_missing(repository, id, e))?;
+        let (digests, validators) = match fetched {
+            download::Download::Complete { digests, validators } => (digests, validators),
+            download::Download::NotModified => return resolver.not_modified(repository, id, path),
+        };
+
+        let downloaded = Downloaded::File {
+            path: &part,
+            digests: &digests,
+        };
+        if let Err(e) = self.verify_download(repository, id, &url, downloaded).await {
+            // it's not worth resuming
+            let _ = std::fs::remove_file(&part);
+            return Err(e);
+        }
+
+        resolver.install_download(repository, id, &part, path, &validators)
+    }
+
+    /// Same as [`Resolver::verify_download`].
+    async fn verify_download(
+        &self,
+        repository: &Repository,
+        id: &Artifact,
+        url: &str,
+        downloaded: Downloaded<'_>,
+    ) -> Result<(), ResolverError> {
+        let digests = match downloaded {
+            Downloaded::Data(data) => std::borrow::Cow::Owned(checksum::Digests::of(data)),
+            Downloaded::File { digests, .. } => std::borrow::Cow::Borrowed(digests),
+        };
+
+        let url_fetcher = self.fetcher_for(repository);
+        let checksum_policy = repository.policy(id).checksum_policy;
+        if checksum_policy != ChecksumPolicy::Ignore {
+            let found = url_fetcher.checksum(url).await?;
+            checksum::check(checksum_policy, url, found, |algorithm| digests.get(algorithm).to_owned())?;
+        }
+
+        #[cfg(feature = "pgp")]
+        let signer = match self.resolver.signature_policy {
+            SignaturePolicy::Skip => None,
+            policy => {
+                let keyring = &self.resolver.keyring;
+                let fetched = url_fetcher.send(&Request::new(&format!("{}.asc", url))).await;
+                let fetched = fetched.map(|response| response.data);
+                match downloaded {
+                    Downloaded::Data(data) => {
+                        signature::check(policy, url, fetched, |signature| keyring.verify(data, signature))?
+                    }
+                    Downloaded::File { path, .. } => {
+                        signature::check(policy, url, fetched, |signature| keyring.verify_file(path, signature))?
+                    }
+                }
+            }
+        }
+        .map(|fingerprint| self.resolver.signed_by(id, fingerprint));
+        #[cfg(not(feature = "pgp"))]
+        let signer: Option<String> = None;
+
+        self.resolver.check_verification_metadata(id, &digests, signer.as_deref())
+    }
+
+    /// Same as [`Resolver::try_download_package`].
+    pub async fn try_download_package(&self, id: &Artifact) -> Result<Packaging, ResolverError> {
+        let resolver = &self.resolver;
+
+        let mut pom_only = false;
+        let handler = match id.packaging.as_deref() {
+            Some(packaging) if packaging != "jar" && packaging != "pom" => {
+                Some(resolver.artifact_handlers.get(packaging))
+            }
+            _ => match self.build_effective_pom(id).await {
+                Ok(project) => {
+                    pom_only = project.packaging.as_deref() == Some("pom");
+                    Some(resolver.packaging_handler(&project))
+                }
+                Err(e) => {
+                    debug!("can't tell the packaging of {}: {}", id, e.msg);
+                    None
+                }
+            },
+        };
+
+        match self.download_package(id, handler.as_ref()).await {
+            // e.g. a BOM or a parent, there's nothing but the POM
+            Err(e) if pom_only && matches!(e.kind, ErrorKind::FileNotFound) => {
+                debug!("{} has no JAR, it's a POM-only project", id);
+                self.fetch_package(&id.with_packaging("pom")).await.map(Packaging::Pom)
+            }
+            result => result,
+        }
+    }
+
+    /// Same as [`Resolver::download_package`].
+    async fn download_package(
+        &self,
+        id: &Artifact,
+        handler: Option<&ArtifactHandler>,
+    ) -> Result<Packaging, ResolverError> {
+        if let Some(handler) = handler {
+            let packaged_id = handler.artifact(id);
+            match self.fetch_package(&packaged_id).await {
+                Ok(path) => return Ok(Packaging::new(&handler.extension, path)),
+                Err(e) if matches!(e.kind, ErrorKind::FileNotFound | ErrorKind::Offline) => {
+                    debug!("{} not found, looking for other packagings", packaged_id)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.probe_package(id).await
+    }
+
+    /// Same as [`Resolver::fetch_package`].
+    async fn fetch_package(&self, id: &Artifact) -> Result<PathBuf, ResolverError> {
+        if let Some(path) = self.resolver.find_local(id)? {
+            return Ok(path);
+        }
+
+        if self.resolver.offline {
+            return Err(ResolverError::offline(id));
+        }
+
+        // if a repository couldn't be reached, it's not known whether the package exists
+        let mut unreachable_error = None;
+
+        for repository in &self.resolver.repositories {
+            match self.download(repository, id).await {
+                Ok(path) => return Ok(path),
+                Err(e) if e.is_unreachable() => {
+                    warn!("{}, trying other repositories", e.msg);
+                    unreachable_error = Some(e);
+                }
+                Err(e) if matches!(e.kind, ErrorKind::FileNotFound) => {}
+                // a corrupted package is not a reason to look elsewhere
+                Err(e) => return Err(e),
+            }
+        }
+
+        match unreachable_error {
+            Some(e) => Err(e),
+            None => Err(ResolverError::file_not_found(&id.to_string())),
+        }
+    }
+
+    /// Same as [`Resolver::probe_package`].
+    async fn probe_package(&self, id: &Artifact) -> Result<Packaging, ResolverError> {
+        const PACKAGINGS: [&str; 2] = ["aar", "jar"];
+
+        for packaging in PACKAGINGS {
+            if let Some(path) = self.resolver.find_local(&id.with_packaging(packaging))? {
+                return Ok(Packaging::new(packaging, path));
+            }
+        }
+
+        if self.resolver.offline {
+            return Err(ResolverError::offline(id));
+        }
+
+        let mut unreachable_error = None;
+
+        for repository in &self.resolver.repositories {
+            for packaging in PACKAGINGS {
+                let packaged_id = id.with_packaging(packaging);
+                let found = self
+                    .fetch_remote(repository, &packaged_id, Request::new, |url_fetcher, request| {
+                        url_fetcher.head(request)
+                    })
+                    .await;
+                match found {
+                    Ok(_) => {
+                        let path = self.download(repository, &packaged_id).await?;
+                        return Ok(Packaging::new(packaging, path));
+                    }
+                    Err(e) if e.is_unreachable() => {
+                        warn!("{}, trying other repositories", e.msg);
+                        unreachable_error = Some(e);
+                    }
+                    Err(e) => debug!("Trying other packaging: {}", e.msg),
+                }
+            }
+        }
+
+        match unreachable_error {
+            Some(e) => Err(e),
+            None => Err(ResolverError::file_not_found(id.artifact_id.as_ref().unwrap())),
+        }
+    }
+
+    pub async fn build_effective_pom(&self, project_id: &Artifact) -> Result<Project, ResolverError> {
+        self.effective_pom(project_id)
+            .await?
+            .map_err(|missing| ResolverError::offline(&missing))
+    }
+
+    /// Same as [`Resolver::effective_pom`].
+    fn effective_pom<'a>(
+        &'a self,
+        project_id: &'a Artifact,
+    ) -> BoxFuture<'a, Result<Result<Project, Artifact>, ResolverError>> {
+        Box::pin(async move {
+            debug!("building an effective pom for {}", project_id);
+
+            let project_id = &project_id.with_packaging("pom");
+
+            // local sources first, then every remote repository
+            let repositories = std::iter::once(None)
+                .chain(self.resolver.repositories.iter().map(|repository| Some(repository.as_ref())));
+
+            // if a repository couldn't be reached, it's not known whether the POM exists
+            let mut unreachable_error = None;
+
+            for repository in repositories {
+                let mut project = match self.load_project(repository, project_id).await {
+                    Ok(project) => project,
+                    Err(e) => {
+                        try_elsewhere(e, &mut unreachable_error)?;
+                        continue;
+                    }
+                };
+
+                if let Some(version) = &project_id.version {
+                    project
+                        .properties
+                        .insert("project.version".to_owned(), version.clone());
+                }
+
+                // merge in the dependencies from the parent POM
+                if let Some(parent) = &project.parent {
+                    let parent_project = match self.effective_pom(&parent.artifact_fqn).await? {
+                        Ok(parent_project) => parent_project,
+                        Err(missing) => return Ok(Err(missing)),
+                    };
+
+                    inherit_dependencies(&mut project, parent_project);
+                }
+
+                if let Some((mut project_dm, boms)) = imported_boms(&project) {
+                    for bom in boms {
+                        trace!("got a BOM artifact: {}", bom.artifact_fqn);
+
+                        let bom_project = match self.effective_pom(&bom.artifact_fqn).await? {
+                            Ok(bom_project) => bom_project,
+                            Err(missing) => return Ok(Err(missing)),
+                        };
+
+                        if let Some(DependencyManagement {
+                            dependencies: bom_deps,
+                        }) = bom_project.dependency_management
+                        {
+                            project_dm.dependencies.extend(bom_deps);
+                        }
+                    }
+                };
+
+                return Ok(Ok(project));
+            }
+
+            self.resolver.project_not_found(project_id, unreachable_error)
+        })
+    }
+
+    /// Same as [`Resolver::load_project`].
+    async fn load_project(
+        &self,
+        repository: Option<&Repository>,
+        project_id: &Artifact,
+    ) -> Result<Project, ResolverError> {
+        let resolver = &self.resolver;
+
+        // we're looking only for POMs here
+        let project_id = project_id.with_packaging("pom");
+
+        if let Some(cached_project) = resolver.cached_project(&project_id) {
+            return Ok(cached_project);
+        }
+
+        // wait if somebody is already loading the same POM, they may have put it in the cache
+        let _loading = self.lock(&project_id).await;
+        let (repository, stale) = match resolver.find_project(repository, &project_id)? {
+            ProjectLookup::Found(project) => return Ok(project),
+            ProjectLookup::Fetch(repository, stale) => (repository, stale),
+        };
+
+        let response = self
+            .fetch_remote(repository, &project_id, |url| stale.request(url), |url_fetcher, request| {
+                url_fetcher.send(request)
+            })
+            .await?;
+        let (text, validators) = match resolver.fetched_project(repository, &project_id, stale, response)? {
+            FetchedProject::Unchanged(project) => return Ok(*project),
+            FetchedProject::Fetched(text, validators) => (text, validators),
+        };
+
+        let url = Resolver::create_url_with_repository(repository, &project_id)?;
+        self.verify_download(repository, &project_id, &url, Downloaded::Data(text.as_bytes()))
+            .await?;
+        resolver.install_project(repository, &project_id, &text, validators)
+    }
+
+    /// Same as [`Resolver::download_all_jars`], the JARs are extracted on tokio's
+    /// blocking thread pool.
+    pub async fn download_all_jars(
+        &self,
+        root_artifacts: &[Artifact],
+        root_directory: &Path,
+    ) -> Result<HashSet<Artifact>, ResolverError> {
+        let mut resolution = ClasspathResolution::new(root_artifacts, root_directory);
+
+        // the graph is resolved level by level, everything on the same level concurrently;
+        // the results are processed in order, so they don't depend on which fetch finishes first
+        loop {
+            let level = resolution.next_level();
+            if level.is_empty() {
+                break;
+            }
+
+            let projects = self
+                .parallel_map(level.clone(), |resolver, dependency| async move {
+                    debug!("Resolving {}...", dependency.artifact_fqn);
+                    resolver.effective_pom(&dependency.artifact_fqn).await
+                })
+                .await;
+            let resolved = resolution.resolved(level, projects)?;
+
+            let to_extract = resolution.extractions(&self.resolver, &resolved);
+            let downloads = self
+                .parallel_map(to_extract.clone(), |resolver, (artifact, handler, extract_path)| {
+                    let artifact_dir = root_directory.join(artifact.artifact_id.as_ref().unwrap());
+                    async move {
+                        let _ = std::fs::create_dir_all(artifact_dir);
+                        let package = resolver.download_package(&artifact, Some(&handler)).await?;
+                        tokio::task::spawn_blocking(move || {
+                            package
+                                .extract_jar_file(&extract_path)
+                                .map_err(|e| ResolverError::io(&extract_path, e))
+                        })
+                        .await
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+                    }
+                })
+                .await;
+            resolution.extracted(&to_extract, downloads)?;
+
+            resolution.add_dependencies(&resolved);
+        }
+
+        resolution.classpath()
+    }
+
+    /// Runs `f` for every item as a task, up to [`Resolver::concurrency`] at the same time.
+    /// The results are in the same order as `items`.
+    async fn parallel_map<T, R, F>(&self, items: Vec<T>, f: impl Fn(AsyncResolver, T) -> F) -> Vec<R>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let permits = Arc::new(tokio::sync::Semaphore::new(self.resolver.concurrency.max(1)));
+        let mut results: Vec<_> = items.iter().map(|_| None).collect();
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let permits = permits.clone();
+            let task = f(self.clone(), item);
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                (i, task.await)
+            });
+        }
+
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok((i, result)) => results[i] = Some(result),
+                Err(e) => std::panic::resume_unwind(e.into_panic()),
+            }
+        }
+
+        results.into_iter().map(|result| result.unwrap()).collect()
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{ResolverError, UrlFetcher};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// What to do when a checksum doesn't match or can't be found,
+/// same as Maven's `<checksumPolicy>`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumPolicy {
+    /// Fail the download.
+    Fail,
+    /// Log a warning and carry on.
+    #[default]
+    Warn,
+    /// Don't fetch the checksum files at all.
+    Ignore,
+}
+
+impl ChecksumPolicy {
+    /// Parses `fail`, `warn` or `ignore`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "fail" => Some(ChecksumPolicy::Fail),
+            "warn" => Some(ChecksumPolicy::Warn),
+            "ignore" => Some(ChecksumPolicy::Ignore),
+            _ => None,
+        }
+    }
+
+    /// The stricter of the two policies.
+    pub fn stricter(self, other: ChecksumPolicy) -> ChecksumPolicy {
+        let strictness = |policy| match policy {
+            ChecksumPolicy::Fail => 2,
+            ChecksumPolicy::Warn => 1,
+            ChecksumPolicy::Ignore => 0,
+        };
+        match strictness(self) >= strictness(other) {
+            true => self,
+            false => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ChecksumAlgorithm {
+    Sha512,
+    Sha256,
+    Sha1,
+    Md5,
+}
+
+impl ChecksumAlgorithm {
+    /// All supported algorithms, strongest first.
+    pub const ALL: [ChecksumAlgorithm; 4] = [
+        ChecksumAlgorithm::Sha512,
+        ChecksumAlgorithm::Sha256,
+        ChecksumAlgorithm::Sha1,
+        ChecksumAlgorithm::Md5,
+    ];
+
+    /// The extension of the sidecar file, e.g. `sha1` for `foo-1.0.jar.sha1`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Sha512 => "sha512",
+            Self::Sha256 => "sha256",
+            Self::Sha1 => "sha1",
+            Self::Md5 => "md5",
+        }
+    }
+
+    /// Returns the lowercase hex digest of `data`.
+    pub fn digest(&self, data: &[u8]) -> String {
+        use sha2::Digest;
+
+        match self {
+            Self::Sha512 => hex::encode(sha2::Sha512::digest(data)),
+            Self::Sha256 => hex::encode(sha2::Sha256::digest(data)),
+            Self::Sha1 => hex::encode(sha1::Sha1::digest(data)),
+            Self::Md5 => hex::encode(md5::Md5::digest(data)),
+        }
+    }
+}
+
+impl std::fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Sha512 => "SHA-512",
+            Self::Sha256 => "SHA-256",
+            Self::Sha1 => "SHA-1",
+            Self::Md5 => "MD5",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Computes the digests of a file in all the supported algorithms as it's written,
+/// so that it doesn't have to be read again to be verified.
+#[derive(Default, Clone)]
+pub struct Hasher {
+    sha512: sha2::Sha512,
+    sha256: sha2::Sha256,
+    sha1: sha1::Sha1,
+    md5: md5::Md5,
+}
+
+impl Hasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+
+        self.sha512.update(data);
+        self.sha256.update(data);
+        self.sha1.update(data);
+        self.md5.update(data);
+    }
+
+    pub fn finish(self) -> Digests {
+        use sha2::Digest;
+
+        Digests(BTreeMap::from([
+            (ChecksumAlgorithm::Sha512, hex::encode(self.sha512.finalize())),
+            (ChecksumAlgorithm::Sha256, hex::encode(self.sha256.finalize())),
+            (ChecksumAlgorithm::Sha1, hex::encode(self.sha1.finalize())),
+            (ChecksumAlgorithm::Md5, hex::encode(self.md5.finalize())),
+        ]))
+    }
+}
+
+impl std::io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The lowercase hex digests of a file in all the supported algorithms.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Digests(BTreeMap<ChecksumAlgorithm, String>);
+
+impl Digests {
+    pub fn of(data: &[u8]) -> Self {
+        let mut hasher = Hasher::new();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    pub fn of_file(path: &Path) -> Result<Self, ResolverError> {
+        let mut hasher = Hasher::new();
+        std::fs::File::open(path)
+            .and_then(|mut file| std::io::copy(&mut file, &mut hasher))
+            .map_err(|e| ResolverError::io(path, e))?;
+        Ok(hasher.finish())
+    }
+
+    pub fn get(&self, algorithm: ChecksumAlgorithm) -> &str {
+        &self.0[&algorithm]
+    }
+}
+
+/// Extracts the hex digest from the contents of a checksum file.
+///
+/// Besides the bare digest, some tools write `<digest>  <file name>` or even
+/// `MD5 (file) = <digest>`, so we look for the first token that looks like a
+/// digest of the right length.
+pub fn parse_checksum_file(algorithm: ChecksumAlgorithm, text: &str) -> Option<String> {
+    let expected_len = algorithm.digest(&[]).len();
+    text.split_whitespace()
+        .find(|token| token.len() == expected_len && token.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|token| token.to_ascii_lowercase())
+}
+
+/// Checks `data` downloaded from `url` against the first checksum file found next to it.
+pub fn verify(
+    url_fetcher: &dyn UrlFetcher,
+    policy: ChecksumPolicy,
+    url: &str,
+    data: &[u8],
+) -> Result<(), ResolverError> {
+    verify_with(url_fetcher, policy, url, |algorithm| algorithm.digest(data))
+}
+
+/// Same as [`verify`] for a file whose digests were computed while it was downloaded.
+pub fn verify_digests(
+    url_fetcher: &dyn UrlFetcher,
+    policy: ChecksumPolicy,
+    url: &str,
+    digests: &Digests,
+) -> Result<(), ResolverError> {
+    verify_with(url_fetcher, policy, url, |algorithm| {
+        digests.get(algorithm).to_owned()
+    })
+}
+
+fn verify_with(
+    url_fetcher: &dyn UrlFetcher,
+    policy: ChecksumPolicy,
+    url: &str,
+    digest: impl FnOnce(ChecksumAlgorithm) -> String,
+) -> Result<(), ResolverError> {
+    if policy == ChecksumPolicy::Ignore {
+        return Ok(());
+    }
+
+    let mut found = None;
+    for algorithm in ChecksumAlgorithm::ALL.iter() {
+        let checksum_url = format!("{}.{}", url, algorithm.extension());
+        match url_fetcher.fetch(&checksum_url) {
+            Ok(text) => {
+                found = Some((*algorithm, parse_checksum_file(*algorithm, &text)));
+                break;
+            }
+            // the checksum file may exist, don't take the file for unchecked
+            Err(e) if e.is_unreachable() => return Err(e),
+            Err(_) => {}
+        }
+    }
+
+    check(policy, url, found, digest)
+}
+
+/// Checks the digest of `url` against the first checksum `found` next to it,
+/// i.e. its algorithm and its value, if the checksum file could be parsed.
+pub(crate) fn check(
+    policy: ChecksumPolicy,
+    url: &str,
+    found: Option<(ChecksumAlgorithm, Option<String>)>,
+    digest: impl FnOnce(ChecksumAlgorithm) -> String,
+) -> Result<(), ResolverError> {
+    if policy == ChecksumPolicy::Ignore {
+        return Ok(());
+    }
+
+    let result = found
+        .ok_or_else(|| ResolverError::checksum_missing(url))
+        .and_then(|(algorithm, expected)| {
+            let actual = digest(algorithm);
+            match expected {
+                Some(expected) if expected == actual => {
+                    debug!("{} checksum of {} is OK", algorithm, crate::credentials::redact(url));
+                    Ok(())
+                }
+                Some(expected) => Err(ResolverError::checksum_mismatch(
+                    url, &algorithm, &expected, &actual,
+                )),
+                None => Err(ResolverError::checksum_missing(url)),
+            }
+        });
+
+    match (result, policy) {
+        (Err(e), ChecksumPolicy::Warn) => {
+            warn!("{}", e.msg);
+            Ok(())
+        }
+        (result, _) => result,
+    }
+}
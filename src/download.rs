@@ -0,0 +1,317 @@
+//! Streams artifacts to disk, resuming interrupted downloads.
+
+use crate::checksum::{Digests, Hasher};
+use crate::{credentials, ErrorKind, FetchPolicy, Request, Response, ResolverError, UrlFetcher, Validators};
+use log::{debug, warn};
+use parking_lot::Mutex;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Where a file from `repository_id` is downloaded before it's moved to `path`.
+///
+/// Each repository gets its own file, so that a download is never resumed
+/// with the data of another repository.
+pub(crate) fn part_path(path: &Path, repository_id: &str) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(format!(".{}.part", repository_id));
+    PathBuf::from(part)
+}
+
+/// What [`download`] got from the repository.
+pub(crate) enum Download {
+    /// The whole file is in the part.
+    Complete {
+        digests: Digests,
+        validators: Validators,
+    },
+    /// The copy that was fetched with the given validators hasn't changed.
+    NotModified,
+}
+
+/// Downloads `url` into `part` and returns the digests of the whole file.
+///
+/// If `part` already has the beginning of the file, e.g. from an interrupted run,
+/// only the rest is requested. Transient failures are retried according to
+/// `fetch_policy`, also asking only for what's still missing. Otherwise, with
+/// the `validators` of an older copy, the request is conditional.
+pub(crate) fn download(
+    url_fetcher: &dyn UrlFetcher,
+    fetch_policy: &FetchPolicy,
+    url: &str,
+    part: &Path,
+    validators: Option<&Validators>,
+) -> Result<Download, ResolverError> {
+    let mut part = Part::open(part)?;
+
+    let mut retry = 0;
+    let result = loop {
+        match fetch(url_fetcher, url, &mut part, validators) {
+            Ok(download) => break Ok(download),
+            Err(e) => match part.failed(url, e, fetch_policy, &mut retry) {
+                Ok(Some(backoff)) => std::thread::sleep(backoff),
+                Ok(None) => {}
+                Err(e) => break Err(e),
+            },
+        }
+    };
+
+    part.close(result)
+}
+
+/// Sends one request for the rest of the file and appends the response to it.
+fn fetch(
+    url_fetcher: &dyn UrlFetcher,
+    url: &str,
+    part: &mut Part,
+    validators: Option<&Validators>,
+) -> Result<Download, ResolverError> {
+    let (response, mut reader) = url_fetcher.send_streaming(&part.request(url, validators))?;
+    if response.is_not_modified() {
+        return Ok(Download::NotModified);
+    }
+    if !part.resumed_by(url, &response)? {
+        // the data can't be appended anywhere, ask for the whole file
+        drop(reader);
+        return fetch(url_fetcher, url, part, None);
+    }
+
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(part.complete(response.validators)),
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            // a connection dropped in the middle of the body, the next request resumes it
+            Err(e) => return Err(ResolverError::transient(url, e.kind())),
+        };
+        part.write(&buffer[..len])?;
+    }
+}
+
+/// Same as [`download`] with an [`AsyncUrlFetcher`](crate::async_resolver::AsyncUrlFetcher).
+#[cfg(feature = "async")]
+pub(crate) async fn download_async(
+    url_fetcher: &dyn crate::async_resolver::AsyncUrlFetcher,
+    fetch_policy: &FetchPolicy,
+    url: &str,
+    part: &Path,
+    validators: Option<&Validators>,
+) -> Result<Download, ResolverError> {
+    let mut part = Part::open(part)?;
+
+    let mut retry = 0;
+    let result = loop {
+        match fetch_async(url_fetcher, url, &mut part, validators).await {
+            Ok(download) => break Ok(download),
+            Err(e) => match part.failed(url, e, fetch_policy, &mut retry) {
+                Ok(Some(backoff)) => tokio::time::sleep(backoff).await,
+                Ok(None) => {}
+                Err(e) => break Err(e),
+            },
+        }
+    };
+
+    part.close(result)
+}
+
+/// Same as [`fetch`] with an [`AsyncUrlFetcher`](crate::async_resolver::AsyncUrlFetcher).
+#[cfg(feature = "async")]
+async fn fetch_async(
+    url_fetcher: &dyn crate::async_resolver::AsyncUrlFetcher,
+    url: &str,
+    part: &mut Part<'_>,
+    validators: Option<&Validators>,
+) -> Result<Download, ResolverError> {
+    let mut validators = validators;
+    loop {
+        let (response, mut body) = url_fetcher.send_streaming(&part.request(url, validators)).await?;
+        if response.is_not_modified() {
+            return Ok(Download::NotModified);
+        }
+        if !part.resumed_by(url, &response)? {
+            // the data can't be appended anywhere, ask for the whole file
+            validators = None;
+            continue;
+        }
+
+        while let Some(chunk) = body.recv().await {
+            part.write(&chunk?)?;
+        }
+        return Ok(part.complete(response.validators));
+    }
+}
+
+/// A file being downloaded, with the digests of what's already in it.
+struct Part<'a> {
+    path: &'a Path,
+    file: std::fs::File,
+    hasher: Hasher,
+    offset: u64,
+}
+
+impl<'a> Part<'a> {
+    fn open(path: &'a Path) -> Result<Self, ResolverError> {
+        let io_error = |e| ResolverError::io(path, e);
+
+        let dir = path.parent().unwrap();
+        std::fs::create_dir_all(dir).map_err(|e| ResolverError::io(dir, e))?;
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_error)?;
+
+        // what's already there has to be hashed too
+        let mut hasher = Hasher::new();
+        let offset = std::io::copy(&mut file, &mut hasher).map_err(io_error)?;
+
+        Ok(Part {
+            path,
+            file,
+            hasher,
+            offset,
+        })
+    }
+
+    /// The request for the rest of the file, conditional if there's nothing yet.
+    fn request(&self, url: &str, validators: Option<&Validators>) -> Request {
+        let mut request = Request::new(url);
+        if self.offset > 0 {
+            debug!("resuming {} from byte {}", credentials::redact(url), self.offset);
+            request = request.with_header("Range", &format!("bytes={}-", self.offset));
+        } else if let Some(validators) = validators {
+            request = request.with_validators(validators);
+        }
+        request
+    }
+
+    /// Whether the data of `response` can be appended to the part. If it's
+    /// the whole file, the part is started over; if it's neither, the whole
+    /// file has to be requested again.
+    fn resumed_by(&mut self, url: &str, response: &Response) -> Result<bool, ResolverError> {
+        if self.offset == 0 {
+            return Ok(true);
+        }
+
+        let start = match response.status {
+            206 => response.content_range.as_deref().and_then(range_start),
+            _ => Some(0),
+        };
+        if start != Some(self.offset) {
+            debug!("{} is sent from byte {:?}", credentials::redact(url), start);
+            self.restart()?;
+            return Ok(start == Some(0));
+        }
+        Ok(true)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), ResolverError> {
+        self.file
+            .write_all(data)
+            .map_err(|e| ResolverError::io(self.path, e))?;
+        self.hasher.update(data);
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+
+    /// The whole file has been written.
+    fn complete(&mut self, validators: Validators) -> Download {
+        Download::Complete {
+            digests: std::mem::take(&mut self.hasher).finish(),
+            validators,
+        }
+    }
+
+    /// Tells how to go on after a failed request: to try again after the returned
+    /// backoff, or right away, or to give up.
+    fn failed(
+        &mut self,
+        url: &str,
+        e: ResolverError,
+        fetch_policy: &FetchPolicy,
+        retry: &mut u32,
+    ) -> Result<Option<std::time::Duration>, ResolverError> {
+        match e {
+            e if e.is_transient() && *retry < fetch_policy.max_retries => {
+                let backoff = fetch_policy.backoff(*retry);
+                warn!("{}, resuming in {:?}", e.msg, backoff);
+                *retry += 1;
+                Ok(Some(backoff))
+            }
+            // e.g. 416 if the part is already complete, or the file is gone
+            e if self.offset > 0 && matches!(e.kind, ErrorKind::FileNotFound) => {
+                debug!("can't resume {}, starting over", credentials::redact(url));
+                self.restart()?;
+                Ok(None)
+            }
+            e => Err(e),
+        }
+    }
+
+    fn restart(&mut self) -> Result<(), ResolverError> {
+        let io_error = |e| ResolverError::io(self.path, e);
+        self.file.set_len(0).map_err(io_error)?;
+        self.file.rewind().map_err(io_error)?;
+        self.hasher = Hasher::new();
+        self.offset = 0;
+        Ok(())
+    }
+
+    fn close(self, result: Result<Download, ResolverError>) -> Result<Download, ResolverError> {
+        // don't leave empty files behind for packagings that don't exist
+        if !matches!(result, Ok(Download::Complete { .. })) && self.offset == 0 {
+            drop(self.file);
+            let _ = std::fs::remove_file(self.path);
+        }
+
+        result
+    }
+}
+
+/// The first byte of a `Content-Range: bytes <first>-<last>/<length>` header.
+fn range_start(content_range: &str) -> Option<u64> {
+    let (first, _) = content_range.trim().strip_prefix("bytes ")?.split_once('-')?;
+    first.trim().parse().ok()
+}
+
+/// Where a resolver without a local repository downloads artifacts.
+///
+/// The directory is created in the system's temporary directory on first use,
+/// and removed along with the resolver.
+#[derive(Debug, Default)]
+pub(crate) struct TempDir {
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl TempDir {
+    pub(crate) fn path(&self) -> Result<PathBuf, ResolverError> {
+        static NEXT_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        let mut path = self.path.lock();
+        if let Some(path) = &*path {
+            return Ok(path.clone());
+        }
+
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("maven-rs-{}-{}", std::process::id(), id));
+        std::fs::create_dir_all(&dir).map_err(|e| ResolverError::io(&dir, e))?;
+        *path = Some(dir.clone());
+        Ok(dir)
+    }
+
+    /// The file at `relative`, if it has been created.
+    pub(crate) fn find(&self, relative: &str) -> Option<PathBuf> {
+        let path = self.path.lock().as_ref()?.join(relative);
+        path.is_file().then_some(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.get_mut() {
+            let _ = std::fs::remove_dir_all(path);
+        }
+    }
+}
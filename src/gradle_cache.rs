@@ -0,0 +1,55 @@
+//! Gradle's dependency cache, i.e. `~/.gradle/caches/modules-2/files-2.1`.
+
+use crate::{Artifact, ArtifactSource};
+use std::path::{Path, PathBuf};
+
+/// A read-only view of Gradle's cache. Files are stored as
+/// `<root>/<groupId>/<artifactId>/<version>/<sha1>/<file name>`.
+#[derive(Debug, Clone)]
+pub struct GradleCache {
+    pub root: PathBuf,
+}
+
+impl GradleCache {
+    pub fn new(root: &Path) -> Self {
+        GradleCache {
+            root: root.to_owned(),
+        }
+    }
+
+    /// `$GRADLE_USER_HOME/caches/modules-2/files-2.1`, or `~/.gradle/...`
+    /// if `GRADLE_USER_HOME` is not set.
+    pub fn user_default() -> Option<Self> {
+        let gradle_home = std::env::var_os("GRADLE_USER_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+                Some(PathBuf::from(home).join(".gradle"))
+            })?;
+        Some(Self::new(
+            &gradle_home.join("caches").join("modules-2").join("files-2.1"),
+        ))
+    }
+}
+
+impl ArtifactSource for GradleCache {
+    fn find(&self, id: &Artifact) -> Option<PathBuf> {
+        let path = id.repository_path().ok()?;
+        let file_name = path.rsplit('/').next()?;
+
+        let dir = self
+            .root
+            .join(id.group_id.as_ref()?)
+            .join(id.artifact_id.as_ref()?)
+            .join(id.version_cleaned()?);
+
+        // there can be more than one hash directory, pick one deterministically
+        let mut candidates = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| Some(entry.ok()?.path().join(file_name)))
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates.into_iter().next()
+    }
+}
@@ -1,41 +1,56 @@
-use log::{debug, trace};
-use std::collections::{HashMap, HashSet, VecDeque};
+use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::cell::RefCell;
+use parking_lot::Mutex;
 
+pub mod artifact_handler;
+#[cfg(feature = "async")]
+pub mod async_resolver;
+pub mod checksum;
+pub mod credentials;
 #[cfg(feature = "default-impl")]
 pub mod default_impl;
-
-pub enum Packaging {
-    Aar(bytes::Bytes),
-    Jar(bytes::Bytes),
-}
-
-impl Packaging {
-    pub fn extract_jar_file(
-        &self,
-        location: &std::path::Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        match self {
-            Self::Aar(bytes) => {
-                use std::io::Read;
-
-                let zip_file = zip::read::ZipArchive::new(std::io::Cursor::new(bytes));
-                let mut bytes = vec![];
-                zip_file?.by_name("classes.jar")?.read_to_end(&mut bytes)?;
-                std::fs::write(location, bytes)?;
-                Ok(())
-            }
-            Self::Jar(bytes) => {
-                std::fs::write(location, bytes)?;
-                Ok(())
-            }
-        }
-    }
-}
-
-#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+mod download;
+pub mod gradle_cache;
+pub mod local_repository;
+pub mod native_libraries;
+pub mod negative_cache;
+pub mod packaging;
+pub mod persistent_cache;
+pub mod prefab;
+pub mod repository_content;
+pub mod repository_policy;
+pub mod resolver_builder;
+pub mod settings;
+#[cfg(feature = "pgp")]
+pub mod signature;
+pub mod verification_metadata;
+
+pub use artifact_handler::{ArtifactHandler, ArtifactHandlers};
+#[cfg(feature = "async")]
+pub use async_resolver::{AsyncResolver, AsyncUrlFetcher, ReqwestUrlFetcher};
+pub use checksum::ChecksumPolicy;
+#[cfg(feature = "default-impl")]
+pub use default_impl::FetcherConfig;
+pub use credentials::{CredentialProvider, Credentials, EnvCredentials, NetrcCredentials};
+pub use gradle_cache::GradleCache;
+pub use local_repository::LocalRepository;
+pub use native_libraries::{Collision, NativeLibraries};
+pub use negative_cache::NegativeCache;
+pub use packaging::Packaging;
+pub use persistent_cache::{CacheEntry, PersistentCache};
+pub use prefab::{PrefabLibrary, PrefabModule, PrefabPackage};
+pub use repository_content::RepositoryContent;
+pub use repository_policy::{FetchPolicy, RepositoryPolicy, UpdatePolicy};
+pub use resolver_builder::ResolverBuilder;
+pub use settings::Settings;
+#[cfg(feature = "pgp")]
+pub use signature::{Fingerprint, Keyring, SignaturePolicy};
+pub use verification_metadata::{VerificationMetadata, VerificationMode};
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Artifact {
     pub group_id: Option<String>,
     pub artifact_id: Option<String>,
@@ -58,7 +73,6 @@ impl Artifact {
             version: Some(version.to_owned()),
             packaging: Some(packaging.to_owned()),
             classifier: Some(classifier.to_owned()),
-            ..Default::default()
         }
     }
 
@@ -108,6 +122,12 @@ impl Artifact {
         }
     }
 
+    pub fn is_snapshot(&self) -> bool {
+        self.version
+            .as_deref()
+            .is_some_and(|version| version.ends_with("-SNAPSHOT"))
+    }
+
     pub fn same_ga(&self, other: &Self) -> bool {
         self.group_id == other.group_id && self.artifact_id == other.artifact_id
     }
@@ -120,15 +140,62 @@ impl Artifact {
             packaging: self
                 .packaging
                 .or_else(|| Some(default_packaging.to_owned())),
-            ..Default::default()
+            classifier: self.classifier,
+        }
+    }
+
+    /// The path of the artifact relative to the root of a repository, e.g.
+    /// `com/example/lib/1.0/lib-1.0-sources.jar`.
+    pub fn repository_path(&self) -> Result<String, ResolverError> {
+        // a little helper
+        fn require<'a, F, D>(
+            id: &'a Artifact,
+            f: F,
+            field_name: &D,
+        ) -> Result<&'a String, ResolverError>
+        where
+            F: Fn(&Artifact) -> Option<&String>,
+            D: std::fmt::Display,
+        {
+            f(id).ok_or_else(|| ResolverError::missing_parameter(id, field_name))
+        }
+
+        let group_id = require(self, |id| id.group_id.as_ref(), &"groupId")?;
+        let artifact_id = require(self, |id| id.artifact_id.as_ref(), &"artifactId")?;
+        let _version = require(self, |id| id.version.as_ref(), &"version")?;
+        let packaging = self.packaging.as_deref().unwrap_or("jar");
+
+        let version = self.version_cleaned().unwrap();
+
+        let mut path = format!(
+            "{}/{}/{}/{}-{}",
+            group_id.replace(".", "/"),
+            artifact_id,
+            version,
+            artifact_id,
+            version
+        );
+
+        if let Some(classifier) = &self.classifier {
+            path += &format!("-{}", classifier);
         }
+
+        path += &format!(".{}", packaging);
+
+        Ok(path)
     }
 
+    /// Where [`Resolver::download_all_jars`] extracts the artifact, e.g. `lib/1.0-tests.jar`.
     pub fn filename(&self) -> PathBuf {
+        let classifier = match &self.classifier {
+            Some(classifier) => format!("-{}", classifier),
+            None => String::new(),
+        };
         PathBuf::from(format!(
-            "{}/{}.{}",
+            "{}/{}{}.{}",
             self.artifact_id.as_ref().unwrap(),
             self.version_cleaned().as_ref().unwrap(),
+            classifier,
             self.packaging.as_ref().unwrap()
         ))
     }
@@ -149,10 +216,14 @@ impl std::fmt::Display for Artifact {
     }
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Dependency {
     pub artifact_fqn: Artifact,
     pub scope: Option<String>,
+    /// The dependency's `<type>`, `jar` if not set. See [`ArtifactHandler`]
+    /// for the file it stands for.
+    #[serde(default)]
+    pub type_name: Option<String>,
 }
 
 impl Dependency {
@@ -167,11 +238,12 @@ impl Dependency {
         Dependency {
             artifact_fqn: self.artifact_fqn.normalize(parent_id, default_packaging),
             scope: self.scope.or_else(|| Some("compile".to_owned())),
+            type_name: self.type_name,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Parent {
     pub artifact_fqn: Artifact,
 }
@@ -194,36 +266,105 @@ impl std::fmt::Display for DependencyKey {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DependencyManagement {
+    #[serde(with = "persistent_cache::dependencies_as_list")]
     pub dependencies: HashMap<DependencyKey, Dependency>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub parent: Option<Parent>,
     pub artifact_fqn: Artifact,
+    /// The project's `<packaging>`, `jar` if not set.
+    #[serde(default)]
+    pub packaging: Option<String>,
     pub dependency_management: Option<DependencyManagement>,
+    #[serde(with = "persistent_cache::dependencies_as_list")]
     pub dependencies: HashMap<DependencyKey, Dependency>,
     pub properties: HashMap<String, String>,
 }
 
+#[derive(Debug, Clone)]
 pub struct Repository {
+    /// The repository's ID, e.g. `central`.
+    pub id: String,
     pub base_url: String,
+    pub releases: RepositoryPolicy,
+    pub snapshots: RepositoryPolicy,
+    /// Which artifacts the repository is asked for.
+    pub content: RepositoryContent,
+    pub credentials: Option<Credentials>,
+    pub fetch_policy: FetchPolicy,
 }
 
 impl Repository {
+    pub fn new(id: &str, base_url: &str) -> Self {
+        Repository {
+            id: id.to_owned(),
+            base_url: base_url.to_owned(),
+            releases: RepositoryPolicy::default(),
+            snapshots: RepositoryPolicy::default(),
+            content: RepositoryContent::default(),
+            credentials: None,
+            fetch_policy: FetchPolicy::default(),
+        }
+    }
+
+    pub fn with_releases(self, releases: RepositoryPolicy) -> Self {
+        Repository { releases, ..self }
+    }
+
+    pub fn with_snapshots(self, snapshots: RepositoryPolicy) -> Self {
+        Repository { snapshots, ..self }
+    }
+
+    /// The policy that applies to `id`, depending on whether it's a snapshot or not.
+    pub fn policy(&self, id: &Artifact) -> &RepositoryPolicy {
+        if id.is_snapshot() {
+            &self.snapshots
+        } else {
+            &self.releases
+        }
+    }
+
+    pub fn with_content(self, content: RepositoryContent) -> Self {
+        Repository { content, ..self }
+    }
+
+    pub fn with_credentials(self, credentials: Credentials) -> Self {
+        Repository {
+            credentials: Some(credentials),
+            ..self
+        }
+    }
+
+    pub fn with_fetch_policy(self, fetch_policy: FetchPolicy) -> Self {
+        Repository {
+            fetch_policy,
+            ..self
+        }
+    }
+
+    /// Sets the checksum policy for both releases and snapshots.
+    pub fn with_checksum_policy(mut self, checksum_policy: ChecksumPolicy) -> Self {
+        self.releases.checksum_policy = checksum_policy;
+        self.snapshots.checksum_policy = checksum_policy;
+        self
+    }
+
     pub fn google_maven() -> Arc<Self> {
-        let base_url = "https://dl.google.com/dl/android/maven2";
-        Arc::new(Self {
-            base_url: base_url.to_string(),
-        })
+        Arc::new(
+            Self::new("google", "https://dl.google.com/dl/android/maven2")
+                .with_snapshots(RepositoryPolicy::disabled()),
+        )
     }
 
     pub fn maven_central() -> Arc<Self> {
-        Arc::new(Repository {
-            base_url: "https://repo.maven.apache.org/maven2".into(),
-        })
+        Arc::new(
+            Self::new("central", "https://repo.maven.apache.org/maven2")
+                .with_snapshots(RepositoryPolicy::disabled()),
+        )
     }
 }
 
@@ -231,6 +372,20 @@ impl Repository {
 pub enum ErrorKind {
     ClientError,
     FileNotFound,
+    ChecksumMismatch,
+    ChecksumMissing,
+    SignatureMissing,
+    SignatureInvalid,
+    SignatureUntrusted,
+    VerificationMetadataMissing,
+    Offline,
+    Io,
+    /// A failure that may go away if retried, e.g. a timeout or a 503.
+    Transient,
+    /// The repository refused the credentials, or asked for some, e.g. a 401 or a 403.
+    Unauthorized,
+    /// The repository can't be reached, e.g. its host name doesn't resolve.
+    Network,
     // RepositoryError,
 }
 
@@ -265,236 +420,1731 @@ impl ResolverError {
     pub fn file_not_found(url: &str) -> Self {
         ResolverError {
             kind: ErrorKind::FileNotFound,
-            msg: format!("Can't find {}", url),
+            msg: format!("Can't find {}", credentials::redact(url)),
         }
     }
-}
 
-pub trait UrlFetcher {
-    fn fetch(&self, url: &str) -> Result<String, ResolverError>;
-    fn fetch_bytes(&self, url: &str) -> Result<bytes::Bytes, ResolverError>;
-}
+    pub fn checksum_mismatch(
+        url: &str,
+        algorithm: &checksum::ChecksumAlgorithm,
+        expected: &str,
+        actual: &str,
+    ) -> Self {
+        ResolverError {
+            kind: ErrorKind::ChecksumMismatch,
+            msg: format!(
+                "{} checksum mismatch for {}: expected {}, got {}",
+                algorithm,
+                credentials::redact(url),
+                expected,
+                actual
+            ),
+        }
+    }
 
-pub trait PomParser {
-    fn parse(&self, input: String) -> Result<Project, ResolverError>;
-}
+    pub fn offline(id: &Artifact) -> Self {
+        ResolverError {
+            kind: ErrorKind::Offline,
+            msg: format!("{} is not available offline", id),
+        }
+    }
 
-pub struct Resolver {
-    pub repositories: Vec<Arc<Repository>>,
-    pub project_cache: RefCell<HashMap<Artifact, Project>>,
+    pub fn missing_offline(ids: &[Artifact]) -> Self {
+        let mut ids = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
 
-    url_fetcher: Box<dyn UrlFetcher>,
-    pom_parser: Box<dyn PomParser>,
-}
+        ResolverError {
+            kind: ErrorKind::Offline,
+            msg: format!(
+                "{} artifact(s) are not available offline:\n  {}",
+                ids.len(),
+                ids.join("\n  ")
+            ),
+        }
+    }
 
-impl Default for Resolver {
-    fn default() -> Self {
-        Resolver {
-            repositories: vec![Repository::maven_central()],
-            project_cache: RefCell::new(HashMap::new()),
-            url_fetcher: Box::new(default_impl::DefaultUrlFetcher {}),
-            pom_parser: Box::new(default_impl::DefaultPomParser {})
+    pub fn io<D: std::fmt::Display>(path: &Path, cause: D) -> Self {
+        ResolverError {
+            kind: ErrorKind::Io,
+            msg: format!("{}: {}", path.display(), cause),
         }
     }
-}
 
-fn normalize_gavs(
-    dependencies: HashMap<DependencyKey, Dependency>,
-    parent_fqn: &Artifact,
-    default_packaging: &str,
-) -> HashMap<DependencyKey, Dependency> {
-    dependencies
-        .into_iter()
-        .map(|(_, dep)| {
-            let dep = dep.normalize(parent_fqn, default_packaging);
-            (dep.get_key(), dep)
-        })
-        .collect()
-}
+    pub fn transient<D: std::fmt::Display>(url: &str, cause: D) -> Self {
+        ResolverError {
+            kind: ErrorKind::Transient,
+            msg: format!("Can't fetch {}: {}", credentials::redact(url), cause),
+        }
+    }
 
-impl Resolver {
-    pub fn new(repositories: &[Arc<Repository>]) -> Self {
-        Self {
-            repositories: repositories.to_vec(),
-            project_cache: RefCell::new(HashMap::new()),
-            url_fetcher: Box::new(default_impl::DefaultUrlFetcher {}),
-            pom_parser: Box::new(default_impl::DefaultPomParser {})
+    pub fn unauthorized(url: &str, status: u16) -> Self {
+        ResolverError {
+            kind: ErrorKind::Unauthorized,
+            msg: format!("Not authorized to fetch {}: HTTP {}", credentials::redact(url), status),
         }
     }
 
-    pub fn try_download_package(
-        &self,
-        id: &Artifact,
-    ) -> Result<Packaging, ResolverError>
-    {
-        for repository in &self.repositories {
-            for packaging in ["aar", "jar"] {
-                let packaged_id = id.with_packaging(packaging);
-                let url = Self::create_url_with_repository(repository, &packaged_id)?;
-                match self.url_fetcher.fetch_bytes(&url) {
-                    Ok(bytes) => {
-                        return Ok(match packaging {
-                            "aar" => Packaging::Aar(bytes),
-                            "jar" => Packaging::Jar(bytes),
-                            _ => unimplemented!("Unsupported packaging type {packaging}"),
-                        });
-                    }
-                    err => debug!("Trying other packaging: {:?}", err),
-                }
-            }
+    pub fn network<D: std::fmt::Display>(url: &str, cause: D) -> Self {
+        ResolverError {
+            kind: ErrorKind::Network,
+            msg: format!("Can't reach {}: {}", credentials::redact(url), cause),
         }
+    }
 
-        Err(ResolverError::file_not_found(&format!(
-            "{}",
-            id.artifact_id.as_ref().unwrap()
-        )))
+    pub fn is_transient(&self) -> bool {
+        matches!(self.kind, ErrorKind::Transient)
     }
 
-    pub fn create_url_with_repository(
-        repository: &Repository,
-        id: &Artifact,
-    ) -> Result<String, ResolverError> {
-        // a little helper
-        fn require<'a, F, D>(
-            id: &'a Artifact,
-            f: F,
-            field_name: &D,
-        ) -> Result<&'a String, ResolverError>
-        where
-            F: Fn(&Artifact) -> Option<&String>,
-            D: std::fmt::Display,
-        {
-            f(id).ok_or_else(|| ResolverError::missing_parameter(id, field_name))
+    /// Whether the repository couldn't tell if the file exists, so that it
+    /// may still be found elsewhere or later.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::Transient | ErrorKind::Unauthorized | ErrorKind::Network
+        )
+    }
+
+    pub fn is_checksum_error(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::ChecksumMismatch
+                | ErrorKind::ChecksumMissing
+                | ErrorKind::VerificationMetadataMissing
+        )
+    }
+
+    pub fn checksum_missing(url: &str) -> Self {
+        ResolverError {
+            kind: ErrorKind::ChecksumMissing,
+            msg: format!(
+                "Can't find a valid checksum file for {}",
+                credentials::redact(url)
+            ),
         }
+    }
 
-        let group_id = require(id, |id| id.group_id.as_ref(), &"groupId")?;
-        let artifact_id = require(id, |id| id.artifact_id.as_ref(), &"artifactId")?;
-        let _version = require(id, |id| id.version.as_ref(), &"version")?;
-        let packaging = id.packaging.as_ref().map(|s| s.as_str()).unwrap_or("jar");
+    pub fn is_signature_error(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::SignatureMissing | ErrorKind::SignatureInvalid | ErrorKind::SignatureUntrusted
+        )
+    }
 
-        let version = id.version_cleaned().unwrap();
+    pub fn signature_missing(url: &str) -> Self {
+        ResolverError {
+            kind: ErrorKind::SignatureMissing,
+            msg: format!("Can't find a signature for {}", credentials::redact(url)),
+        }
+    }
 
-        let mut url = format!(
-            "{}/{}/{}/{}/{}-{}",
-            repository.base_url,
-            group_id.replace(".", "/"),
-            artifact_id,
-            version,
-            artifact_id,
-            version
-        );
+    pub fn signature_invalid(details: &str) -> Self {
+        ResolverError {
+            kind: ErrorKind::SignatureInvalid,
+            msg: format!("Invalid signature: {}", details),
+        }
+    }
+
+    pub fn verification_metadata_missing(id: &Artifact, file_name: &str) -> Self {
+        ResolverError {
+            kind: ErrorKind::VerificationMetadataMissing,
+            msg: format!(
+                "Dependency verification failed for {}: no checksums or trusted keys for {}",
+                id, file_name
+            ),
+        }
+    }
 
-        if let Some(classifier) = &id.classifier {
-            url += &format!("-{}", classifier);
+    pub fn signature_untrusted(issuer: &str) -> Self {
+        ResolverError {
+            kind: ErrorKind::SignatureUntrusted,
+            msg: format!("Signed by {}, which is not in the keyring", issuer),
         }
+    }
+}
 
-        url += &format!(".{}", packaging);
+/// A GET request with extra headers, such as the repository's credentials.
+#[derive(Clone)]
+pub struct Request {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// The fetcher's own timeouts apply if not set.
+    pub connect_timeout: Option<std::time::Duration>,
+    pub read_timeout: Option<std::time::Duration>,
+}
 
-        Ok(url)
+impl Request {
+    pub fn new(url: &str) -> Self {
+        Request {
+            url: url.to_owned(),
+            headers: vec![],
+            connect_timeout: None,
+            read_timeout: None,
+        }
     }
 
-    pub fn build_effective_pom(
-        &self,
-        project_id: &Artifact,
-    ) -> Result<Project, ResolverError>
-    {
-        debug!("building an effective pom for {}", project_id);
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
 
-        let project_id = &project_id.with_packaging("pom");
-        for repository in &self.repositories {
-            let Ok(mut project) =
-                self.fetch_project(repository, project_id)
-            else {
-                continue;
-            };
+    /// Makes the request conditional, i.e. the server responds with 304
+    /// if the file hasn't changed since it was fetched with `validators`.
+    pub fn with_validators(mut self, validators: &Validators) -> Self {
+        if let Some(etag) = &validators.etag {
+            self = self.with_header("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            self = self.with_header("If-Modified-Since", last_modified);
+        }
+        self
+    }
+}
 
-            if let Some(version) = &project_id.version {
-                project
-                    .properties
-                    .insert("project.version".to_owned(), version.clone());
-            }
+/// Identify a version of a remote file, see [`Request::with_validators`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    /// The `ETag` header.
+    pub etag: Option<String>,
+    /// The `Last-Modified` header.
+    pub last_modified: Option<String>,
+}
 
-            // merge in the dependencies from the parent POM
-            if let Some(parent) = &project.parent {
-                let parent_project =
-                    self.build_effective_pom(&parent.artifact_fqn)?;
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    /// Empty if the file wasn't modified.
+    pub data: bytes::Bytes,
+    pub validators: Validators,
+    /// The `Content-Range` header of a partial response.
+    pub content_range: Option<String>,
+}
 
-                trace!("got a parent POM: {}", parent_project.artifact_fqn);
+impl Response {
+    pub fn is_not_modified(&self) -> bool {
+        self.status == 304
+    }
+}
 
-                let extra_deps = parent_project
-                    .dependencies
-                    .into_iter()
-                    .filter(|(dep_key, _)| !project.dependencies.contains_key(dep_key))
-                    .collect::<HashMap<_, _>>();
+// the headers usually carry credentials, never print them
+impl std::fmt::Debug for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headers: Vec<_> = self.headers.iter().map(|(name, _)| (name, "***")).collect();
+        f.debug_struct("Request")
+            .field("url", &credentials::redact(&self.url))
+            .field("headers", &headers)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .finish()
+    }
+}
 
-                project.dependencies.extend(extra_deps);
-            }
+pub trait UrlFetcher: Send + Sync {
+    fn fetch(&self, url: &str) -> Result<String, ResolverError>;
+    fn fetch_bytes(&self, url: &str) -> Result<bytes::Bytes, ResolverError>;
 
-            if let Some(mut project_dm) = project.dependency_management.clone() {
-                for (_, dep) in &mut project_dm.dependencies {
-                    dep.artifact_fqn = dep.artifact_fqn.interpolate(&project.properties);
-                }
+    /// Same as [`UrlFetcher::fetch`], but sends the request's headers too.
+    /// Fetchers that don't override it can't access authenticated repositories.
+    fn fetch_request(&self, request: &Request) -> Result<String, ResolverError> {
+        self.fetch(&request.url)
+    }
 
-                let boms: Vec<Dependency> = project_dm
-                    .dependencies
-                    .iter()
-                    .filter(|(_, dep)| dep.scope.as_deref() == Some("import"))
-                    .map(|(_, dep)| dep.clone())
-                    .collect();
+    /// Same as [`UrlFetcher::fetch_bytes`], but sends the request's headers too.
+    fn fetch_bytes_request(&self, request: &Request) -> Result<bytes::Bytes, ResolverError> {
+        self.fetch_bytes(&request.url)
+    }
 
-                for bom in boms {
-                    trace!("got a BOM artifact: {}", bom.artifact_fqn);
+    /// Sends the request and returns the response along with its metadata.
+    /// Fetchers that don't override it never report a file as not modified.
+    fn send(&self, request: &Request) -> Result<Response, ResolverError> {
+        Ok(Response {
+            status: 200,
+            data: self.fetch_bytes_request(request)?,
+            validators: Validators::default(),
+            content_range: None,
+        })
+    }
 
-                    // TODO add protection against infinite recursion
-                    let bom_project =
-                        self.build_effective_pom(&bom.artifact_fqn)?;
+    /// Same as [`UrlFetcher::send`], but the data is read from the returned reader
+    /// instead of being buffered in the response, so that large files can be
+    /// written to disk as they arrive.
+    ///
+    /// Fetchers that don't override it buffer the data anyway, and can't resume
+    /// downloads: the `Range` header is dropped, so that the whole file is sent.
+    fn send_streaming(&self, request: &Request) -> Result<StreamingResponse<'_>, ResolverError> {
+        let mut request = request.clone();
+        request
+            .headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("Range"));
+        let mut response = self.send(&request)?;
+        let data = std::mem::take(&mut response.data);
+        Ok((response, Box::new(std::io::Cursor::new(data))))
+    }
 
-                    if let Some(DependencyManagement {
-                        dependencies: bom_deps,
-                    }) = bom_project.dependency_management
-                    {
-                        project_dm.dependencies.extend(bom_deps);
-                    }
-                }
-            };
+    /// Checks whether a file exists without downloading it, i.e. sends a `HEAD` request.
+    /// Fetchers that don't override it send a `GET` request and drop the data.
+    fn head(&self, request: &Request) -> Result<Response, ResolverError> {
+        self.send_streaming(request).map(|(response, _)| response)
+    }
+}
 
-            return Ok(project);
-        }
+/// A response without its data, and the reader of the data.
+pub type StreamingResponse<'a> = (Response, Box<dyn std::io::Read + Send + 'a>);
 
-        Err(ResolverError::file_not_found(&format!("{}", project_id)))
+/// Fetches from a particular repository: sends its credentials along with every request,
+/// applies its timeouts and retries transient failures.
+struct RepositoryFetcher<'a> {
+    url_fetcher: &'a dyn UrlFetcher,
+    headers: Vec<(String, String)>,
+    fetch_policy: FetchPolicy,
+}
+
+impl RepositoryFetcher<'_> {
+    /// Adds the repository's credentials and timeouts to the request.
+    fn prepare(&self, request: &Request) -> Request {
+        let mut request = request.clone();
+        request.headers.extend(self.headers.iter().cloned());
+        request.connect_timeout = request.connect_timeout.or(Some(self.fetch_policy.connect_timeout));
+        request.read_timeout = request.read_timeout.or(Some(self.fetch_policy.read_timeout));
+        request
     }
 
-    pub fn fetch_project(
+    fn with_retries<T>(
         &self,
-        repository: &Repository,
-        project_id: &Artifact,
-    ) -> Result<Project, ResolverError>
-    {
-        // we're looking only for POMs here
-        let project_id = project_id.with_packaging("pom");
-
-        // check the cache first
-        if let Some(cached_project) = self.project_cache.borrow().get(&project_id) {
-            debug!("returning from cache {}...", project_id);
-            return Ok(cached_project.clone());
+        request: &Request,
+        fetch: impl Fn(&Request) -> Result<T, ResolverError>,
+    ) -> Result<T, ResolverError> {
+        let request = self.prepare(request);
+
+        let mut retry = 0;
+        loop {
+            match fetch(&request) {
+                Err(e) if e.is_transient() && retry < self.fetch_policy.max_retries => {
+                    let backoff = self.fetch_policy.backoff(retry);
+                    warn!("{}, retrying in {:?}", e.msg, backoff);
+                    std::thread::sleep(backoff);
+                    retry += 1;
+                }
+                result => return result,
+            }
         }
+    }
+}
 
-        // grab the remote POM
-        let url = Self::create_url_with_repository(repository, &project_id)?;
+impl UrlFetcher for RepositoryFetcher<'_> {
+    fn fetch(&self, url: &str) -> Result<String, ResolverError> {
+        self.fetch_request(&Request::new(url))
+    }
 
-        debug!("fetching {}...", url);
-        let text = self.url_fetcher.fetch(&url)?;
+    fn fetch_bytes(&self, url: &str) -> Result<bytes::Bytes, ResolverError> {
+        self.fetch_bytes_request(&Request::new(url))
+    }
 
-        // parse the POM - it will be our "root" project
-        // TODO handle multiple "roots"
-        let mut project = self.pom_parser.parse(text)?;
+    fn fetch_request(&self, request: &Request) -> Result<String, ResolverError> {
+        self.with_retries(request, |request| self.url_fetcher.fetch_request(request))
+    }
 
-        // make sure the packaging type is set to "pom"
-        let mut project_id = project.artifact_fqn.with_packaging("pom");
+    fn fetch_bytes_request(&self, request: &Request) -> Result<bytes::Bytes, ResolverError> {
+        self.with_retries(request, |request| self.url_fetcher.fetch_bytes_request(request))
+    }
 
-        // TODO consider moving this to build_effective_pom
-        // update the parent and fill-in the project's missing properties using the parent's GAV
-        if let Some(parent) = &project.parent {
+    fn send(&self, request: &Request) -> Result<Response, ResolverError> {
+        self.with_retries(request, |request| self.url_fetcher.send(request))
+    }
+
+    fn head(&self, request: &Request) -> Result<Response, ResolverError> {
+        self.with_retries(request, |request| self.url_fetcher.head(request))
+    }
+
+    // not retried here, the download resumes where the failed attempt stopped
+    fn send_streaming(&self, request: &Request) -> Result<StreamingResponse<'_>, ResolverError> {
+        self.url_fetcher.send_streaming(&self.prepare(request))
+    }
+}
+
+pub trait PomParser: Send + Sync {
+    fn parse(&self, input: String) -> Result<Project, ResolverError>;
+}
+
+/// A local, read-only source of POMs and artifacts.
+pub trait ArtifactSource: Send + Sync {
+    /// Returns the path of the file if the source has it.
+    fn find(&self, id: &Artifact) -> Option<PathBuf>;
+
+    fn read(&self, id: &Artifact) -> Result<Option<Vec<u8>>, ResolverError> {
+        let Some(path) = self.find(id) else {
+            return Ok(None);
+        };
+        trace!("found {} at {}", id, path.display());
+        std::fs::read(&path)
+            .map(Some)
+            .map_err(|e| ResolverError::io(&path, e))
+    }
+}
+
+/// Resolves projects and downloads their artifacts.
+///
+/// It's `Send + Sync`, so one resolver (e.g. in an `Arc`) can serve concurrent
+/// resolutions, which then share its caches.
+pub struct Resolver {
+    pub repositories: Vec<Arc<Repository>>,
+    pub project_cache: Mutex<HashMap<Artifact, Project>>,
+
+    /// Keys trusted to sign artifacts.
+    #[cfg(feature = "pgp")]
+    pub keyring: Keyring,
+    #[cfg(feature = "pgp")]
+    pub signature_policy: SignaturePolicy,
+    /// Fingerprints of the keys that signed the downloaded POMs and artifacts.
+    #[cfg(feature = "pgp")]
+    pub signers: Mutex<HashMap<Artifact, Fingerprint>>,
+
+    /// Gradle-compatible dependency verification metadata, checked or
+    /// updated (depending on `verification_mode`) with every resolved file,
+    /// whether it's downloaded or found locally.
+    pub verification_metadata: Mutex<Option<VerificationMetadata>>,
+    pub verification_mode: VerificationMode,
+
+    /// Never go to the network, use only what's already cached locally.
+    pub offline: bool,
+
+    /// Checked before the remote repositories, everything downloaded is stored here.
+    /// Without one, the downloads go to a temporary directory, which is removed
+    /// along with the resolver.
+    pub local_repository: Option<LocalRepository>,
+    /// Checked after the local repository, e.g. Gradle's cache.
+    pub artifact_sources: Vec<Box<dyn ArtifactSource>>,
+    /// Parsed projects saved between runs.
+    pub persistent_cache: Option<PersistentCache>,
+    /// Files known to be missing from the remote repositories.
+    pub negative_cache: NegativeCache,
+    /// Ignore the negative cache and the update policies, and ask the remote
+    /// repositories again, same as Maven's `-U`.
+    pub force_refresh: bool,
+    /// Where to look for the credentials of repositories that don't have any set, in order.
+    pub credential_providers: Vec<Box<dyn CredentialProvider>>,
+    /// How many POMs or artifacts are fetched at the same time, `1` to fetch them one by one.
+    pub concurrency: usize,
+    /// What the dependency types and the packagings stand for.
+    pub artifact_handlers: ArtifactHandlers,
+
+    url_fetcher: Box<dyn UrlFetcher>,
+    pom_parser: Box<dyn PomParser>,
+    /// Held while a POM is being loaded or an artifact is being downloaded, so that
+    /// concurrent resolutions wait for it instead of fetching it again.
+    locks: Mutex<HashMap<Artifact, Arc<Mutex<()>>>>,
+    /// Where artifacts are downloaded if there's no local repository.
+    downloads: download::TempDir,
+}
+
+/// The lock of an artifact, removed from the resolver's table by its last holder.
+struct ArtifactLock<'a> {
+    locks: &'a Mutex<HashMap<Artifact, Arc<Mutex<()>>>>,
+    id: Artifact,
+    guard: Option<parking_lot::ArcMutexGuard<parking_lot::RawMutex, ()>>,
+}
+
+impl Drop for ArtifactLock<'_> {
+    fn drop(&mut self) {
+        // the waiters clone the lock while holding the table, so nobody can
+        // pick it up between the release and the removal
+        let mut locks = self.locks.lock();
+        self.guard.take();
+        if locks.get(&self.id).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            locks.remove(&self.id);
+        }
+    }
+}
+
+/// Where [`Resolver::download`] puts a file.
+enum DownloadTarget {
+    /// It's already there.
+    Found(PathBuf),
+    /// With the validators of the local copy that's due for an update.
+    Download {
+        path: PathBuf,
+        validators: Option<Validators>,
+    },
+}
+
+/// What [`Resolver::find_project`] found.
+enum ProjectLookup<'r> {
+    Found(Project),
+    /// The project has to be fetched from the repository.
+    Fetch(&'r Repository, StaleProject),
+}
+
+/// The copies of a project that are due for an update, still good if the
+/// repository says they haven't changed.
+struct StaleProject {
+    entry: Option<CacheEntry>,
+    /// The validators and the path of the copy in the local repository.
+    copy: Option<(Validators, PathBuf)>,
+}
+
+impl StaleProject {
+    /// The request for the project, conditional if there's a stale copy.
+    fn request(&self, url: &str) -> Request {
+        let validators = match (&self.entry, &self.copy) {
+            (Some(entry), _) => Some(&entry.validators),
+            (None, Some((validators, _))) => Some(validators),
+            (None, None) => None,
+        };
+        let mut request = Request::new(url);
+        if let Some(validators) = validators {
+            request = request.with_validators(validators);
+        }
+        request
+    }
+}
+
+/// A POM fetched from a repository, see [`Resolver::loaded_project`].
+struct Fetched<'a> {
+    repository: &'a Repository,
+    validators: Validators,
+    digests: checksum::Digests,
+}
+
+/// What [`Resolver::fetched_project`] made of a response.
+enum FetchedProject {
+    Unchanged(Box<Project>),
+    /// A new POM and its validators.
+    Fetched(String, Validators),
+}
+
+/// A freshly downloaded file.
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "pgp"), allow(dead_code))]
+enum Downloaded<'a> {
+    Data(&'a [u8]),
+    File { path: &'a Path, digests: &'a checksum::Digests },
+}
+
+/// What [`Resolver::download_all_jars`] has resolved so far.
+struct ClasspathResolution {
+    root_directory: PathBuf,
+    /// The dependencies to resolve next.
+    level: Vec<Dependency>,
+    done: HashSet<Artifact>,
+    /// What's missing locally in offline mode.
+    missing: Vec<Artifact>,
+    extracted: HashSet<PathBuf>,
+    classpath: HashSet<Artifact>,
+}
+
+/// A file to download and extract: the artifact, its handler, and where to extract it.
+type Extraction = (Artifact, ArtifactHandler, PathBuf);
+
+impl ClasspathResolution {
+    fn new(root_artifacts: &[Artifact], root_directory: &Path) -> Self {
+        // the roots are projects, their packaging tells what to download
+        let level = root_artifacts
+            .iter()
+            .map(|artifact| Dependency {
+                artifact_fqn: artifact.clone(),
+                ..Default::default()
+            })
+            .collect();
+        ClasspathResolution {
+            root_directory: root_directory.to_owned(),
+            level,
+            done: HashSet::new(),
+            missing: vec![],
+            extracted: HashSet::new(),
+            classpath: HashSet::new(),
+        }
+    }
+
+    /// The dependencies of the next level that haven't been resolved yet.
+    fn next_level(&mut self) -> Vec<Dependency> {
+        let mut level = std::mem::take(&mut self.level);
+        level.retain(|dependency| self.done.insert(dependency.artifact_fqn.clone()));
+        level
+    }
+
+    /// Pairs the dependencies with their effective POMs, and notes the POMs
+    /// that are missing offline.
+    fn resolved(
+        &mut self,
+        level: Vec<Dependency>,
+        projects: Vec<Result<Result<Project, Artifact>, ResolverError>>,
+    ) -> Result<Vec<(Dependency, Project)>, ResolverError> {
+        let mut resolved = vec![];
+        for (dependency, project) in level.into_iter().zip(projects) {
+            match project? {
+                Ok(project) => resolved.push((dependency, project)),
+                // the POM of the dependency, or of one of its parents
+                Err(pom) if !self.missing.contains(&pom) => self.missing.push(pom),
+                Err(_) => {}
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// The files of `resolved` to download and extract, everything on the classpath
+    /// is added to it.
+    fn extractions(&mut self, resolver: &Resolver, resolved: &[(Dependency, Project)]) -> Vec<Extraction> {
+        let verifying = resolver.verification_metadata.lock().is_some();
+        resolved
+            .iter()
+            .filter_map(|(dependency, project)| {
+                let handler = resolver.dependency_handler(dependency, project);
+                if !handler.added_to_classpath {
+                    debug!(
+                        "{} is a {}, not added to the classpath",
+                        dependency.artifact_fqn, handler.type_name
+                    );
+                    return None;
+                }
+
+                let id = Artifact {
+                    classifier: dependency.artifact_fqn.classifier.clone(),
+                    ..project.artifact_fqn.clone()
+                };
+                // e.g. `lib/1.0-tests.jar` for a `test-jar`
+                let jar = handler.artifact(&id).with_packaging("jar");
+                let extract_path = self.root_directory.join(jar.filename());
+                self.classpath.insert(jar);
+                Some((id, handler, extract_path))
+            })
+            // a snapshot may have changed since it was extracted, and an extracted
+            // JAR can't be verified on its own, only the package it came from
+            .filter(|(id, _, extract_path)| {
+                (id.is_snapshot() || verifying || !extract_path.exists())
+                    && self.extracted.insert(extract_path.clone())
+            })
+            .collect()
+    }
+
+    /// Notes the JARs that are missing offline, or fails with the first other error.
+    fn extracted(
+        &mut self,
+        to_extract: &[Extraction],
+        downloads: Vec<Result<(), ResolverError>>,
+    ) -> Result<(), ResolverError> {
+        for ((artifact, _, _), download) in to_extract.iter().zip(downloads) {
+            match download {
+                Ok(()) => {}
+                Err(e) if matches!(e.kind, ErrorKind::Offline) => {
+                    self.missing.push(artifact.with_packaging("jar"));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the compile dependencies of `resolved` the next level.
+    fn add_dependencies(&mut self, resolved: &[(Dependency, Project)]) {
+        self.level = resolved
+            .iter()
+            .flat_map(|(_, project)| {
+                let mut dependencies: Vec<_> = project
+                    .dependencies
+                    .values()
+                    .filter(|dep| dep.scope.as_deref() == Some("compile"))
+                    .cloned()
+                    .collect();
+                // the dependencies are kept in a HashMap
+                dependencies.sort_by_key(|dep| dep.artifact_fqn.to_string());
+                dependencies
+            })
+            .collect();
+    }
+
+    /// The JARs on the classpath, unless something is missing offline.
+    fn classpath(self) -> Result<HashSet<Artifact>, ResolverError> {
+        if !self.missing.is_empty() {
+            return Err(ResolverError::missing_offline(&self.missing));
+        }
+
+        Ok(self.classpath)
+    }
+}
+
+#[cfg(feature = "default-impl")]
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver::new(&[Repository::maven_central()])
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+pub(crate) fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// When the file was last modified, in seconds since the Unix epoch.
+fn modified_at(path: &Path) -> u64 {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+fn normalize_gavs(
+    dependencies: HashMap<DependencyKey, Dependency>,
+    parent_fqn: &Artifact,
+    default_packaging: &str,
+) -> HashMap<DependencyKey, Dependency> {
+    dependencies
+        .into_values()
+        .map(|dep| {
+            let dep = dep.normalize(parent_fqn, default_packaging);
+            (dep.get_key(), dep)
+        })
+        .collect()
+}
+
+/// Whether to look for a file in the other repositories after `e`, remembering
+/// the last repository that couldn't be reached.
+fn try_elsewhere(e: ResolverError, unreachable_error: &mut Option<ResolverError>) -> Result<(), ResolverError> {
+    // a corrupted file is not a reason to look elsewhere
+    if e.is_checksum_error() || e.is_signature_error() {
+        return Err(e);
+    }
+    if e.is_unreachable() {
+        warn!("{}, trying other repositories", e.msg);
+        *unreachable_error = Some(e);
+    }
+    Ok(())
+}
+
+/// Merges in the dependencies from the parent POM.
+fn inherit_dependencies(project: &mut Project, parent_project: Project) {
+    trace!("got a parent POM: {}", parent_project.artifact_fqn);
+
+    let extra_deps = parent_project
+        .dependencies
+        .into_iter()
+        .filter(|(dep_key, _)| !project.dependencies.contains_key(dep_key))
+        .collect::<HashMap<_, _>>();
+
+    project.dependencies.extend(extra_deps);
+}
+
+/// The project's dependency management, interpolated, and the BOMs it imports.
+fn imported_boms(project: &Project) -> Option<(DependencyManagement, Vec<Dependency>)> {
+    let mut project_dm = project.dependency_management.clone()?;
+    for dep in project_dm.dependencies.values_mut() {
+        dep.artifact_fqn = dep.artifact_fqn.interpolate(&project.properties);
+    }
+
+    let boms: Vec<Dependency> = project_dm
+        .dependencies
+        .iter()
+        .filter(|(_, dep)| dep.scope.as_deref() == Some("import"))
+        .map(|(_, dep)| dep.clone())
+        .collect();
+
+    Some((project_dm, boms))
+}
+
+impl Resolver {
+    /// A resolver with the default fetcher and parser, see [`ResolverBuilder`] for more options.
+    #[cfg(feature = "default-impl")]
+    pub fn new(repositories: &[Arc<Repository>]) -> Self {
+        Self::builder()
+            .with_repositories(repositories)
+            .build()
+            .expect("the default implementations are available")
+    }
+
+    pub fn builder() -> ResolverBuilder {
+        ResolverBuilder::new()
+    }
+
+    /// Replaces the network configuration (proxy, TLS) of the default URL fetcher,
+    /// which otherwise comes from the environment.
+    #[cfg(feature = "default-impl")]
+    pub fn with_fetcher_config(
+        self,
+        config: default_impl::FetcherConfig,
+    ) -> Result<Self, ResolverError> {
+        Ok(Resolver {
+            url_fetcher: Box::new(default_impl::DefaultUrlFetcher::new(config)?),
+            ..self
+        })
+    }
+
+    /// Creates a resolver configured by the `settings.xml` at `path`: Maven Central and
+    /// the repositories of the active profiles behind the configured mirrors, the
+    /// credentials of the servers, the local repository and the offline mode.
+    #[cfg(feature = "default-impl")]
+    pub fn from_settings(path: &Path) -> Result<Self, ResolverError> {
+        let settings = Settings::from_file(path)?;
+        let mut resolver = Self::new(&settings.repositories(&[Repository::maven_central()]));
+        resolver.local_repository = match &settings.local_repository {
+            Some(root) => Some(LocalRepository::new(root)),
+            None => LocalRepository::user_default(),
+        };
+        resolver.offline = settings.offline;
+        Ok(resolver)
+    }
+
+    /// Same as [`Resolver::from_settings`] with `~/.m2/settings.xml`, or the defaults
+    /// if there's no such file.
+    #[cfg(feature = "default-impl")]
+    pub fn from_user_settings() -> Result<Self, ResolverError> {
+        match Settings::user_default_path().filter(|path| path.is_file()) {
+            Some(path) => Self::from_settings(&path),
+            None => Ok(Self {
+                local_repository: LocalRepository::user_default(),
+                ..Self::default()
+            }),
+        }
+    }
+
+    /// The credentials of `repository`, either its own or from the first provider that has any.
+    fn credentials(&self, repository: &Repository) -> Option<Credentials> {
+        repository.credentials.clone().or_else(|| {
+            self.credential_providers
+                .iter()
+                .find_map(|provider| provider.credentials(repository))
+        })
+    }
+
+    /// A fetcher sending the credentials of `repository` along with every request.
+    fn fetcher_for(&self, repository: &Repository) -> RepositoryFetcher<'_> {
+        RepositoryFetcher {
+            url_fetcher: self.url_fetcher.as_ref(),
+            fetch_policy: repository.fetch_policy,
+            headers: self
+                .credentials(repository)
+                .map(|credentials| credentials.header())
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Fetches a file from a remote repository, unless it's known to be missing there.
+    fn fetch_remote<T>(
+        &self,
+        repository: &Repository,
+        id: &Artifact,
+        fetch: impl FnOnce(&dyn UrlFetcher, &str) -> Result<T, ResolverError>,
+    ) -> Result<T, ResolverError> {
+        let url = self.remote_url(repository, id)?;
+        fetch(&self.fetcher_for(repository), &url).inspect_err(|e| self.note_missing(repository, id, e))
+    }
+
+    /// The URL to fetch `id` from, unless `repository` isn't asked for it
+    /// or it's known to be missing there.
+    fn remote_url(&self, repository: &Repository, id: &Artifact) -> Result<String, ResolverError> {
+        let url = Self::create_url_with_repository(repository, id)?;
+        let path = id.repository_path()?;
+
+        if !repository.content.matches(id) || !repository.policy(id).enabled {
+            trace!("{} is not asked for {}", repository.id, id);
+            return Err(ResolverError::file_not_found(&url));
+        }
+
+        if !self.force_refresh && self.negative_cache.is_missing(&repository.id, &path) {
+            debug!("skipping {}, it's known to be missing", credentials::redact(&url));
+            return Err(ResolverError::file_not_found(&url));
+        }
+
+        debug!("fetching {}...", credentials::redact(&url));
+        Ok(url)
+    }
+
+    /// Remembers that `id` is missing from `repository` if that's what the fetch failed with.
+    fn note_missing(&self, repository: &Repository, id: &Artifact, e: &ResolverError) {
+        // only a repository that answered knows the file is missing, not one
+        // that refused the credentials or couldn't be reached
+        if matches!(e.kind, ErrorKind::FileNotFound) {
+            if let Ok(path) = id.repository_path() {
+                self.negative_cache.mark_missing(&repository.id, &path);
+            }
+        }
+    }
+
+    /// Waits until nobody else is loading or downloading `id`, and holds
+    /// its lock until the returned guard is dropped.
+    fn lock(&self, id: &Artifact) -> ArtifactLock<'_> {
+        let lock = self.locks.lock().entry(id.clone()).or_default().clone();
+        ArtifactLock {
+            locks: &self.locks,
+            id: id.clone(),
+            guard: Some(lock.lock_arc()),
+        }
+    }
+
+    /// The local repository, then the other local sources.
+    fn local_sources(&self) -> impl Iterator<Item = &dyn ArtifactSource> {
+        let local_repository = self
+            .local_repository
+            .as_ref()
+            .map(|local_repository| local_repository as &dyn ArtifactSource);
+
+        local_repository
+            .into_iter()
+            .chain(self.artifact_sources.iter().map(|source| source.as_ref()))
+    }
+
+    /// The local copies of `id` that can be used without asking the repositories:
+    /// from the local sources, then what this resolver downloaded.
+    fn local_copies<'a>(&'a self, id: &'a Artifact) -> impl Iterator<Item = PathBuf> + 'a {
+        self.local_sources()
+            .filter_map(|source| source.find(id))
+            .filter(|path| self.is_fresh(id, path))
+            .chain(self.downloaded(id))
+    }
+
+    /// Whether a local copy of `id` is recent enough. Releases never change, but a
+    /// snapshot is checked for updates according to the update policy of the
+    /// repositories it was downloaded from, or, if that's not known, of all the
+    /// repositories that have it.
+    fn is_fresh(&self, id: &Artifact, path: &Path) -> bool {
+        if !id.is_snapshot() || self.offline {
+            return true;
+        }
+
+        let origins = match &self.local_repository {
+            Some(local_repository) if path.starts_with(&local_repository.root) => {
+                let origins = local_repository.origins(id).unwrap_or_default();
+                // e.g. built with `mvn install`, there's nothing to update it from
+                if origins.is_empty() {
+                    return true;
+                }
+                Some(origins)
+            }
+            _ => None,
+        };
+
+        let fetched_at = modified_at(path);
+        let now = unix_time();
+
+        let stale = self.repositories.iter().any(|repository| {
+            let policy = repository.policy(id);
+            let update_policy = match self.force_refresh {
+                true => UpdatePolicy::Always,
+                false => policy.update_policy,
+            };
+            origins.as_ref().is_none_or(|origins| origins.contains(&repository.id))
+                && policy.enabled
+                && repository.content.matches(id)
+                && update_policy.is_stale(fetched_at, now)
+        });
+        if stale {
+            debug!("{} at {} is due for an update", id, path.display());
+        }
+        !stale
+    }
+
+    /// Looks for a POM in the local repository and the other local sources.
+    /// A copy that can't be parsed, e.g. a truncated download, is skipped.
+    fn read_local_project(&self, id: &Artifact) -> Result<Option<Project>, ResolverError> {
+        for path in self.local_copies(id) {
+            trace!("found {} at {}", id, path.display());
+            let bytes = std::fs::read(&path).map_err(|e| ResolverError::io(&path, e))?;
+            match self.pom_parser.parse(String::from_utf8_lossy(&bytes).into_owned()) {
+                Ok(project) => {
+                    self.verify_local_data(id, &bytes)?;
+                    return Ok(Some(project));
+                }
+                Err(e) => warn!("ignoring the local copy of {}: {}", id, e.msg),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Looks for a file in the local repository and the other local sources,
+    /// and returns its path.
+    fn find_local(&self, id: &Artifact) -> Result<Option<PathBuf>, ResolverError> {
+        let Some(path) = self.local_copies(id).next() else {
+            return Ok(None);
+        };
+        trace!("found {} at {}", id, path.display());
+        self.verify_local(id, &path)?;
+        Ok(Some(path))
+    }
+
+    /// A file this resolver already downloaded to its temporary directory. It was
+    /// verified when it was written, so it's not fetched again.
+    fn downloaded(&self, id: &Artifact) -> Option<PathBuf> {
+        if self.local_repository.is_some() || self.force_refresh {
+            return None;
+        }
+        self.downloads.find(&id.repository_path().ok()?)
+    }
+
+    /// Downloads an artifact from a remote repository through a temporary file,
+    /// and moves it into the local repository once it's verified.
+    fn download(&self, repository: &Repository, id: &Artifact) -> Result<PathBuf, ResolverError> {
+        let _downloading = self.lock(id);
+
+        let (path, validators) = match self.download_target(repository, id)? {
+            DownloadTarget::Found(path) => return Ok(path),
+            DownloadTarget::Download { path, validators } => (path, validators),
+        };
+        let part = download::part_path(&path, &repository.id);
+
+        let fetched = self.fetch_remote(repository, id, |url_fetcher, url| {
+            download::download(url_fetcher, &repository.fetch_policy, url, &part, validators.as_ref())
+        })?;
+        let (digests, validators) = match fetched {
+            download::Download::Complete { digests, validators } => (digests, validators),
+            download::Download::NotModified => return self.not_modified(repository, id, path),
+        };
+
+        let url = Self::create_url_with_repository(repository, id)?;
+        let downloaded = Downloaded::File {
+            path: &part,
+            digests: &digests,
+        };
+        if let Err(e) = self.verify_download(repository, id, &url, downloaded) {
+            // it's not worth resuming
+            let _ = std::fs::remove_file(&part);
+            return Err(e);
+        }
+
+        self.install_download(repository, id, &part, path, &validators)
+    }
+
+    /// Where to download `id` to, unless somebody else has just downloaded it.
+    fn download_target(&self, repository: &Repository, id: &Artifact) -> Result<DownloadTarget, ResolverError> {
+        match &self.local_repository {
+            Some(local_repository) => {
+                let local_copy = local_repository.find(id);
+                if let Some(path) = local_copy.as_ref().filter(|path| self.is_fresh(id, path)) {
+                    self.verify_local(id, path)?;
+                    return Ok(DownloadTarget::Found(path.clone()));
+                }
+                // the validators of a snapshot that's due for an update
+                let validators = local_copy.and_then(|path| local_repository.validators(&path, &repository.id));
+                Ok(DownloadTarget::Download {
+                    path: local_repository.path_of(id)?,
+                    validators,
+                })
+            }
+            None => match self.downloaded(id) {
+                Some(path) => {
+                    self.verify_local(id, &path)?;
+                    Ok(DownloadTarget::Found(path))
+                }
+                None => Ok(DownloadTarget::Download {
+                    path: self.downloads.path()?.join(id.repository_path()?),
+                    validators: None,
+                }),
+            },
+        }
+    }
+
+    /// The local copy of `id` at `path` is still the same as in `repository`.
+    fn not_modified(&self, repository: &Repository, id: &Artifact, path: PathBuf) -> Result<PathBuf, ResolverError> {
+        debug!("{} hasn't changed in {}", id, repository.id);
+        local_repository::touch(&path)?;
+        self.verify_local(id, &path)?;
+        Ok(path)
+    }
+
+    /// Moves a verified download from `part` to the local repository, or to `path`
+    /// if there's none.
+    fn install_download(
+        &self,
+        repository: &Repository,
+        id: &Artifact,
+        part: &Path,
+        path: PathBuf,
+        validators: &Validators,
+    ) -> Result<PathBuf, ResolverError> {
+        match &self.local_repository {
+            Some(local_repository) => {
+                let path = local_repository.install_file(id, &repository.id, part)?;
+                // releases never change, there's no need to revalidate them
+                if id.is_snapshot() {
+                    local_repository.set_validators(&path, &repository.id, validators)?;
+                }
+                Ok(path)
+            }
+            None => std::fs::rename(part, &path)
+                .map(|_| path.clone())
+                .map_err(|e| ResolverError::io(&path, e)),
+        }
+    }
+
+    /// Checks the checksum and the signature of a freshly downloaded file.
+    fn verify_download(
+        &self,
+        repository: &Repository,
+        id: &Artifact,
+        url: &str,
+        downloaded: Downloaded,
+    ) -> Result<(), ResolverError> {
+        let digests = match downloaded {
+            Downloaded::Data(data) => std::borrow::Cow::Owned(checksum::Digests::of(data)),
+            Downloaded::File { digests, .. } => std::borrow::Cow::Borrowed(digests),
+        };
+
+        let url_fetcher = self.fetcher_for(repository);
+        checksum::verify_digests(
+            &url_fetcher,
+            repository.policy(id).checksum_policy,
+            url,
+            &digests,
+        )?;
+
+        #[cfg(feature = "pgp")]
+        let signer = match downloaded {
+            Downloaded::Data(data) => signature::verify(
+                &url_fetcher,
+                &self.keyring,
+                self.signature_policy,
+                url,
+                data,
+            )?,
+            Downloaded::File { path, .. } => signature::verify_file(
+                &url_fetcher,
+                &self.keyring,
+                self.signature_policy,
+                url,
+                path,
+            )?,
+        }
+        .map(|fingerprint| self.signed_by(id, fingerprint));
+        #[cfg(not(feature = "pgp"))]
+        let signer: Option<String> = None;
+
+        self.check_verification_metadata(id, &digests, signer.as_deref())
+    }
+
+    /// Checks a file that wasn't just downloaded, e.g. from the local repository,
+    /// against the verification metadata, or records it. Its signature is known
+    /// only if this resolver has verified it before.
+    fn verify_local(&self, id: &Artifact, path: &Path) -> Result<(), ResolverError> {
+        if self.verification_metadata.lock().is_none() {
+            return Ok(());
+        }
+        let digests = checksum::Digests::of_file(path)?;
+        self.check_verification_metadata(id, &digests, self.signer(id).as_deref())
+    }
+
+    /// Same as [`Resolver::verify_local`] for a file that's already been read.
+    fn verify_local_data(&self, id: &Artifact, data: &[u8]) -> Result<(), ResolverError> {
+        if self.verification_metadata.lock().is_none() {
+            return Ok(());
+        }
+        let digests = checksum::Digests::of(data);
+        self.check_verification_metadata(id, &digests, self.signer(id).as_deref())
+    }
+
+    /// Checks a project from the persistent cache against the verification metadata,
+    /// or records it.
+    fn verify_cached(&self, id: &Artifact, entry: &CacheEntry) -> Result<(), ResolverError> {
+        match &entry.digests {
+            Some(digests) => self.check_verification_metadata(id, digests, entry.signer.as_deref()),
+            None => Ok(()),
+        }
+    }
+
+    /// The fingerprint of the key that signed `id`, if this resolver has verified it.
+    #[cfg(feature = "pgp")]
+    fn signer(&self, id: &Artifact) -> Option<String> {
+        self.signers.lock().get(id).map(ToString::to_string)
+    }
+
+    #[cfg(not(feature = "pgp"))]
+    fn signer(&self, _id: &Artifact) -> Option<String> {
+        None
+    }
+
+    /// Remembers who signed `id`.
+    #[cfg(feature = "pgp")]
+    fn signed_by(&self, id: &Artifact, fingerprint: signature::Fingerprint) -> String {
+        let signer = fingerprint.to_string();
+        self.signers.lock().insert(id.clone(), fingerprint);
+        signer
+    }
+
+    /// Verifies the file of `id` against the verification metadata, or records it.
+    fn check_verification_metadata(
+        &self,
+        id: &Artifact,
+        digests: &checksum::Digests,
+        signer: Option<&str>,
+    ) -> Result<(), ResolverError> {
+        if let Some(metadata) = self.verification_metadata.lock().as_mut() {
+            let path = id.repository_path()?;
+            let file_name = path.rsplit('/').next().unwrap_or(&path);
+            match self.verification_mode {
+                VerificationMode::Verify => metadata.verify_digests(id, file_name, digests, signer)?,
+                VerificationMode::Record(algorithm) => {
+                    metadata.record_digests(id, file_name, digests, algorithm, signer)
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Downloads the package of `id`.
+    ///
+    /// The file is chosen by the [`ArtifactHandler`] of the packaging of `id`, i.e. of the
+    /// dependency type, or, if that's `jar`, `pom` or not set, of the project's `<packaging>`.
+    /// Only if there's no such file, the repositories are probed for an AAR or a JAR,
+    /// and a project with the `pom` packaging and no JAR is a [`Packaging::Pom`].
+    ///
+    /// Without a [`Resolver::local_repository`], the file is removed when the resolver
+    /// is dropped, copy it somewhere else to keep it.
+    pub fn try_download_package(
+        &self,
+        id: &Artifact,
+    ) -> Result<Packaging, ResolverError>
+    {
+        let mut pom_only = false;
+        let handler = match id.packaging.as_deref() {
+            Some(packaging) if packaging != "jar" && packaging != "pom" => {
+                Some(self.artifact_handlers.get(packaging))
+            }
+            _ => match self.build_effective_pom(id) {
+                Ok(project) => {
+                    pom_only = project.packaging.as_deref() == Some("pom");
+                    Some(self.packaging_handler(&project))
+                }
+                Err(e) => {
+                    debug!("can't tell the packaging of {}: {}", id, e.msg);
+                    None
+                }
+            },
+        };
+
+        match self.download_package(id, handler.as_ref()) {
+            // e.g. a BOM or a parent, there's nothing but the POM
+            Err(e) if pom_only && matches!(e.kind, ErrorKind::FileNotFound) => {
+                debug!("{} has no JAR, it's a POM-only project", id);
+                self.fetch_package(&id.with_packaging("pom")).map(Packaging::Pom)
+            }
+            result => result,
+        }
+    }
+
+    /// The handler of the project's `<packaging>`.
+    fn packaging_handler(&self, project: &Project) -> ArtifactHandler {
+        // same as Maven, depending on a `pom` project with the default type means its JAR
+        let packaging = project
+            .packaging
+            .as_deref()
+            .filter(|packaging| *packaging != "pom")
+            .unwrap_or("jar");
+        self.artifact_handlers.get(packaging)
+    }
+
+    /// The handler of a dependency's type, or of the project's packaging if the type is
+    /// the default one, so that e.g. AARs are found even if the type is not set.
+    fn dependency_handler(&self, dependency: &Dependency, project: &Project) -> ArtifactHandler {
+        match dependency.type_name.as_deref() {
+            Some(type_name) if type_name != "jar" => self.artifact_handlers.get(type_name),
+            _ => self.packaging_handler(project),
+        }
+    }
+
+    /// Downloads the file described by `handler`, or probes for an AAR or a JAR.
+    fn download_package(
+        &self,
+        id: &Artifact,
+        handler: Option<&ArtifactHandler>,
+    ) -> Result<Packaging, ResolverError> {
+        if let Some(handler) = handler {
+            let packaged_id = handler.artifact(id);
+            match self.fetch_package(&packaged_id) {
+                Ok(path) => return Ok(Packaging::new(&handler.extension, path)),
+                Err(e) if matches!(e.kind, ErrorKind::FileNotFound | ErrorKind::Offline) => {
+                    debug!("{} not found, looking for other packagings", packaged_id)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.probe_package(id)
+    }
+
+    /// Looks for `id` locally, then downloads it from the first repository that has it.
+    fn fetch_package(&self, id: &Artifact) -> Result<PathBuf, ResolverError> {
+        if let Some(path) = self.find_local(id)? {
+            return Ok(path);
+        }
+
+        if self.offline {
+            return Err(ResolverError::offline(id));
+        }
+
+        // if a repository couldn't be reached, it's not known whether the package exists
+        let mut unreachable_error = None;
+
+        for repository in &self.repositories {
+            match self.download(repository, id) {
+                Ok(path) => return Ok(path),
+                Err(e) if e.is_unreachable() => {
+                    warn!("{}, trying other repositories", e.msg);
+                    unreachable_error = Some(e);
+                }
+                Err(e) if matches!(e.kind, ErrorKind::FileNotFound) => {}
+                // a corrupted package is not a reason to look elsewhere
+                Err(e) => return Err(e),
+            }
+        }
+
+        match unreachable_error {
+            Some(e) => Err(e),
+            None => Err(ResolverError::file_not_found(&id.to_string())),
+        }
+    }
+
+    /// Looks for an AAR or a JAR of `id`. The repositories are asked with `HEAD`
+    /// requests, and only the package that exists is downloaded.
+    fn probe_package(&self, id: &Artifact) -> Result<Packaging, ResolverError> {
+        const PACKAGINGS: [&str; 2] = ["aar", "jar"];
+
+        for packaging in PACKAGINGS {
+            if let Some(path) = self.find_local(&id.with_packaging(packaging))? {
+                return Ok(Packaging::new(packaging, path));
+            }
+        }
+
+        if self.offline {
+            return Err(ResolverError::offline(id));
+        }
+
+        let mut unreachable_error = None;
+
+        for repository in &self.repositories {
+            for packaging in PACKAGINGS {
+                let packaged_id = id.with_packaging(packaging);
+                let found = self.fetch_remote(repository, &packaged_id, |url_fetcher, url| {
+                    url_fetcher.head(&Request::new(url))
+                });
+                match found {
+                    Ok(_) => {
+                        let path = self.download(repository, &packaged_id)?;
+                        return Ok(Packaging::new(packaging, path));
+                    }
+                    Err(e) if e.is_unreachable() => {
+                        warn!("{}, trying other repositories", e.msg);
+                        unreachable_error = Some(e);
+                    }
+                    Err(e) => debug!("Trying other packaging: {}", e.msg),
+                }
+            }
+        }
+
+        match unreachable_error {
+            Some(e) => Err(e),
+            None => Err(ResolverError::file_not_found(id.artifact_id.as_ref().unwrap())),
+        }
+    }
+
+    pub fn create_url_with_repository(
+        repository: &Repository,
+        id: &Artifact,
+    ) -> Result<String, ResolverError> {
+        Ok(format!("{}/{}", repository.base_url, id.repository_path()?))
+    }
+
+    pub fn build_effective_pom(
+        &self,
+        project_id: &Artifact,
+    ) -> Result<Project, ResolverError>
+    {
+        self.effective_pom(project_id)?
+            .map_err(|missing| ResolverError::offline(&missing))
+    }
+
+    /// Same as [`Resolver::build_effective_pom`], but in offline mode a POM that's
+    /// missing locally, e.g. the parent of `project_id`, is returned as `Ok(Err(id))`.
+    fn effective_pom(
+        &self,
+        project_id: &Artifact,
+    ) -> Result<Result<Project, Artifact>, ResolverError>
+    {
+        debug!("building an effective pom for {}", project_id);
+
+        let project_id = &project_id.with_packaging("pom");
+
+        // local sources first, then every remote repository
+        let repositories = std::iter::once(None)
+            .chain(self.repositories.iter().map(|repository| Some(repository.as_ref())));
+
+        // if a repository couldn't be reached, it's not known whether the POM exists
+        let mut unreachable_error = None;
+
+        for repository in repositories {
+            let mut project = match self.load_project(repository, project_id) {
+                Ok(project) => project,
+                Err(e) => {
+                    try_elsewhere(e, &mut unreachable_error)?;
+                    continue;
+                }
+            };
+
+            if let Some(version) = &project_id.version {
+                project
+                    .properties
+                    .insert("project.version".to_owned(), version.clone());
+            }
+
+            // merge in the dependencies from the parent POM
+            if let Some(parent) = &project.parent {
+                let parent_project = match self.effective_pom(&parent.artifact_fqn)? {
+                    Ok(parent_project) => parent_project,
+                    Err(missing) => return Ok(Err(missing)),
+                };
+
+                inherit_dependencies(&mut project, parent_project);
+            }
+
+            if let Some((mut project_dm, boms)) = imported_boms(&project) {
+                for bom in boms {
+                    trace!("got a BOM artifact: {}", bom.artifact_fqn);
+
+                    // TODO add protection against infinite recursion
+                    let bom_project = match self.effective_pom(&bom.artifact_fqn)? {
+                        Ok(bom_project) => bom_project,
+                        Err(missing) => return Ok(Err(missing)),
+                    };
+
+                    if let Some(DependencyManagement {
+                        dependencies: bom_deps,
+                    }) = bom_project.dependency_management
+                    {
+                        project_dm.dependencies.extend(bom_deps);
+                    }
+                }
+            };
+
+            return Ok(Ok(project));
+        }
+
+        self.project_not_found(project_id, unreachable_error)
+    }
+
+    /// The result of [`Resolver::effective_pom`] when no repository has the project.
+    fn project_not_found(
+        &self,
+        project_id: &Artifact,
+        unreachable_error: Option<ResolverError>,
+    ) -> Result<Result<Project, Artifact>, ResolverError> {
+        if self.offline {
+            return Ok(Err(project_id.clone()));
+        }
+
+        if let Some(e) = unreachable_error {
+            return Err(e);
+        }
+
+        Err(ResolverError::file_not_found(&format!("{}", project_id)))
+    }
+
+    pub fn fetch_project(
+        &self,
+        repository: &Repository,
+        project_id: &Artifact,
+    ) -> Result<Project, ResolverError>
+    {
+        self.load_project(Some(repository), project_id)
+    }
+
+    /// Fetches `maven-metadata.xml` of `group_id:artifact_id`, or of one of its versions,
+    /// e.g. to find the latest version or the timestamped files of a snapshot.
+    ///
+    /// With a [`Resolver::local_repository`], the file is kept there and fetched again
+    /// according to the update policy of `repository`, with a conditional request
+    /// if the repository sent an `ETag` or `Last-Modified` the last time.
+    pub fn fetch_metadata(
+        &self,
+        repository: &Repository,
+        group_id: &str,
+        artifact_id: &str,
+        version: Option<&str>,
+    ) -> Result<bytes::Bytes, ResolverError> {
+        let id = Artifact {
+            group_id: Some(group_id.to_owned()),
+            artifact_id: Some(artifact_id.to_owned()),
+            version: version.map(str::to_owned),
+            ..Default::default()
+        };
+        let mut url = format!("{}/{}/{}", repository.base_url, group_id.replace('.', "/"), artifact_id);
+        if let Some(version) = version {
+            url += &format!("/{}", version);
+        }
+        url += "/maven-metadata.xml";
+
+        // the metadata of an artifact lists both its releases and its snapshots
+        let policies: Vec<_> = match version {
+            Some(_) => vec![repository.policy(&id)],
+            None => vec![&repository.releases, &repository.snapshots],
+        }
+        .into_iter()
+        .filter(|policy| policy.enabled)
+        .collect();
+        if policies.is_empty() || !repository.content.matches(&id) {
+            trace!("{} is not asked for {}", repository.id, id);
+            return Err(ResolverError::file_not_found(&url));
+        }
+
+        let read = |path: &Path| {
+            std::fs::read(path)
+                .map(bytes::Bytes::from)
+                .map_err(|e| ResolverError::io(path, e))
+        };
+
+        let local_copy = self.local_repository.as_ref().and_then(|local_repository| {
+            let path = local_repository.metadata_path(group_id, artifact_id, version, &repository.id);
+            path.is_file().then_some((local_repository, path))
+        });
+        if let Some((_, path)) = &local_copy {
+            let fetched_at = modified_at(path);
+            let now = unix_time();
+            // offline, a stale copy is better than nothing
+            let fresh = self.offline
+                || !self.force_refresh
+                    && !policies
+                        .iter()
+                        .any(|policy| policy.update_policy.is_stale(fetched_at, now));
+            if fresh {
+                trace!("found the metadata of {} at {}", id, path.display());
+                return read(path);
+            }
+        }
+
+        if self.offline {
+            return Err(ResolverError::offline(&id));
+        }
+
+        let mut request = Request::new(&url);
+        if let Some(validators) = local_copy
+            .as_ref()
+            .and_then(|(local_repository, path)| local_repository.validators(path, &repository.id))
+        {
+            request = request.with_validators(&validators);
+        }
+
+        debug!("fetching {}...", credentials::redact(&url));
+        let url_fetcher = self.fetcher_for(repository);
+        let response = url_fetcher.send(&request)?;
+        if let Some((_, path)) = local_copy.as_ref().filter(|_| response.is_not_modified()) {
+            debug!("the metadata of {} hasn't changed in {}", id, repository.id);
+            local_repository::touch(path)?;
+            return read(path);
+        }
+
+        checksum::verify_digests(
+            &url_fetcher,
+            policies[0].checksum_policy,
+            &url,
+            &checksum::Digests::of(&response.data),
+        )?;
+
+        if let Some(local_repository) = &self.local_repository {
+            let path = local_repository.install_metadata(group_id, artifact_id, version, &repository.id, &response.data)?;
+            local_repository.set_validators(&path, &repository.id, &response.validators)?;
+        }
+
+        Ok(response.data)
+    }
+
+    /// Loads a project from the cache, the local sources or, if given, the remote `repository`.
+    fn load_project(
+        &self,
+        repository: Option<&Repository>,
+        project_id: &Artifact,
+    ) -> Result<Project, ResolverError>
+    {
+        // we're looking only for POMs here
+        let project_id = project_id.with_packaging("pom");
+
+        // check the cache first
+        if let Some(cached_project) = self.cached_project(&project_id) {
+            return Ok(cached_project);
+        }
+
+        // wait if somebody is already loading the same POM, they may have put it in the cache
+        let _loading = self.lock(&project_id);
+        let (repository, stale) = match self.find_project(repository, &project_id)? {
+            ProjectLookup::Found(project) => return Ok(project),
+            ProjectLookup::Fetch(repository, stale) => (repository, stale),
+        };
+
+        // grab the remote POM
+        let response = self.fetch_remote(repository, &project_id, |url_fetcher, url| {
+            url_fetcher.send(&stale.request(url))
+        })?;
+        let (text, validators) = match self.fetched_project(repository, &project_id, stale, response)? {
+            FetchedProject::Unchanged(project) => return Ok(*project),
+            FetchedProject::Fetched(text, validators) => (text, validators),
+        };
+
+        let url = Self::create_url_with_repository(repository, &project_id)?;
+        self.verify_download(repository, &project_id, &url, Downloaded::Data(text.as_bytes()))?;
+        self.install_project(repository, &project_id, &text, validators)
+    }
+
+    fn cached_project(&self, project_id: &Artifact) -> Option<Project> {
+        let cached_project = self.project_cache.lock().get(project_id).cloned()?;
+        debug!("returning from cache {}...", project_id);
+        Some(cached_project)
+    }
+
+    /// Looks for a project in the caches and the local sources, or tells
+    /// what's needed to fetch it from `repository`.
+    fn find_project<'r>(
+        &self,
+        repository: Option<&'r Repository>,
+        project_id: &Artifact,
+    ) -> Result<ProjectLookup<'r>, ResolverError> {
+        // somebody else may have just loaded it
+        if let Some(cached_project) = self.cached_project(project_id) {
+            return Ok(ProjectLookup::Found(cached_project));
+        }
+
+        // a stale entry is still good enough if the repository says it hasn't changed
+        let mut stale_entry = None;
+
+        if let Some((repository, persistent_cache)) = repository.zip(self.persistent_cache.as_ref()) {
+            let update_policy = match self.force_refresh {
+                true => UpdatePolicy::Always,
+                false => repository.policy(project_id).update_policy,
+            };
+            // an entry without the digests of its POM can't be verified
+            let entry = persistent_cache
+                .entry(&repository.id, project_id)
+                .filter(|entry| entry.digests.is_some() || self.verification_metadata.lock().is_none());
+            match entry {
+                // offline, a stale entry is better than nothing
+                Some(entry) if self.offline || entry.is_fresh(project_id, update_policy) => {
+                    debug!("returning from the persistent cache {}...", project_id);
+                    self.verify_cached(project_id, &entry)?;
+                    self.project_cache
+                        .lock()
+                        .insert(entry.project.artifact_fqn.clone(), entry.project.clone());
+                    return Ok(ProjectLookup::Found(entry.project));
+                }
+                Some(entry) => stale_entry = Some(entry),
+                None => {}
+            }
+        }
+
+        if let Some(project) = self.read_local_project(project_id)? {
+            return Ok(ProjectLookup::Found(self.loaded_project(project_id, project, None)));
+        }
+
+        let repository = match repository {
+            None => return Err(ResolverError::file_not_found(&project_id.to_string())),
+            Some(_) if self.offline => return Err(ResolverError::offline(project_id)),
+            Some(repository) => repository,
+        };
+
+        // a snapshot in the local repository that's due for an update
+        let stale_copy = match &self.local_repository {
+            Some(local_repository) if stale_entry.is_none() => local_repository
+                .find(project_id)
+                .and_then(|path| Some((local_repository.validators(&path, &repository.id)?, path))),
+            _ => None,
+        };
+
+        Ok(ProjectLookup::Fetch(
+            repository,
+            StaleProject {
+                entry: stale_entry,
+                copy: stale_copy,
+            },
+        ))
+    }
+
+    /// Takes the response of `repository` for a project: a stale copy that hasn't
+    /// changed is used as it is, a new POM has yet to be verified.
+    fn fetched_project(
+        &self,
+        repository: &Repository,
+        project_id: &Artifact,
+        stale: StaleProject,
+        response: Response,
+    ) -> Result<FetchedProject, ResolverError> {
+        if let Some(entry) = stale.entry.filter(|_| response.is_not_modified()) {
+            debug!("{} hasn't changed in {}", project_id, repository.id);
+            self.verify_cached(project_id, &entry)?;
+            if let Some(persistent_cache) = &self.persistent_cache {
+                // the validators may be missing in a 304 response
+                let entry = CacheEntry {
+                    fetched_at: unix_time(),
+                    ..entry.clone()
+                };
+                if let Err(e) = persistent_cache.put_entry(&repository.id, project_id, &entry) {
+                    warn!("can't update {} in the persistent cache: {}", project_id, e.msg);
+                }
+            }
+            self.project_cache
+                .lock()
+                .insert(entry.project.artifact_fqn.clone(), entry.project.clone());
+            return Ok(FetchedProject::Unchanged(Box::new(entry.project)));
+        }
+
+        if let Some((validators, path)) = stale.copy.filter(|_| response.is_not_modified()) {
+            debug!("{} hasn't changed in {}", project_id, repository.id);
+            local_repository::touch(&path)?;
+            let bytes = std::fs::read(&path).map_err(|e| ResolverError::io(&path, e))?;
+            let project = self.pom_parser.parse(String::from_utf8_lossy(&bytes).into_owned())?;
+            self.verify_local_data(project_id, &bytes)?;
+            let fetched = Fetched {
+                repository,
+                validators,
+                digests: checksum::Digests::of(&bytes),
+            };
+            let project = self.loaded_project(project_id, project, Some(fetched));
+            return Ok(FetchedProject::Unchanged(Box::new(project)));
+        }
+
+        let url = Self::create_url_with_repository(repository, project_id)?;
+        let text = String::from_utf8(response.data.to_vec()).map_err(|_| {
+            ResolverError::invalid_data(&format!("{} is not valid UTF-8", credentials::redact(&url)))
+        })?;
+        Ok(FetchedProject::Fetched(text, response.validators))
+    }
+
+    /// Parses a verified POM from `repository` and stores it in the local repository.
+    fn install_project(
+        &self,
+        repository: &Repository,
+        project_id: &Artifact,
+        text: &str,
+        validators: Validators,
+    ) -> Result<Project, ResolverError> {
+        // nothing that can't be parsed is installed
+        let project = self.pom_parser.parse(text.to_owned())?;
+
+        if let Some(local_repository) = &self.local_repository {
+            let path = local_repository.install(project_id, &repository.id, text.as_bytes())?;
+            if project_id.is_snapshot() {
+                local_repository.set_validators(&path, &repository.id, &validators)?;
+            }
+        }
+
+        let fetched = Fetched {
+            repository,
+            validators,
+            digests: checksum::Digests::of(text.as_bytes()),
+        };
+        Ok(self.loaded_project(project_id, project, Some(fetched)))
+    }
+
+    /// Fills in what a freshly loaded project inherits from its parent, and caches it.
+    fn loaded_project(
+        &self,
+        requested_id: &Artifact,
+        mut project: Project,
+        fetched: Option<Fetched>,
+    ) -> Project {
+        // make sure the packaging type is set to "pom"
+        let mut project_id = project.artifact_fqn.with_packaging("pom");
+
+        // TODO consider moving this to build_effective_pom
+        // update the parent and fill-in the project's missing properties using the parent's GAV
+        if let Some(parent) = &project.parent {
             let parent_fqn = parent.artifact_fqn.with_packaging("pom");
 
             project_id = project_id.normalize(&parent_fqn, "pom");
@@ -520,59 +2170,98 @@ impl Resolver {
         // as a "cache"
         trace!("caching {}", project_id);
         self.project_cache
-            .borrow_mut()
+            .lock()
             .insert(project_id, project.clone());
 
-        Ok(project)
+        if let Some((fetched, persistent_cache)) = fetched.zip(self.persistent_cache.as_ref()) {
+            let entry = CacheEntry {
+                fetched_at: unix_time(),
+                validators: fetched.validators,
+                project: project.clone(),
+                digests: Some(fetched.digests),
+                signer: self.signer(requested_id),
+            };
+            if let Err(e) = persistent_cache.put_entry(&fetched.repository.id, requested_id, &entry) {
+                warn!("can't save {} in the persistent cache: {}", requested_id, e.msg);
+            }
+        }
+
+        project
     }
 
+    /// Resolves `root_artifacts` and their compile dependencies and extracts
+    /// their JARs into `root_directory`.
+    ///
+    /// Returns the JARs on the classpath, i.e. without the dependencies whose
+    /// [`ArtifactHandler`] doesn't add them to it, e.g. BOMs. Each one is extracted
+    /// to its [`Artifact::filename`] in `root_directory`.
+    ///
+    /// In offline mode all the artifacts that are missing locally are
+    /// reported at once.
     pub fn download_all_jars(
         &self,
         root_artifacts: &[Artifact],
         root_directory: &Path,
-    ) -> HashSet<Artifact>
+    ) -> Result<HashSet<Artifact>, ResolverError>
     {
-        let mut todo = VecDeque::new();
-        todo.extend(root_artifacts.iter().cloned());
-
-        let mut done = HashSet::new();
-
-        while let Some(artifact) = todo.pop_front() {
-            if !done.insert(artifact.clone()) {
-                continue;
+        let mut resolution = ClasspathResolution::new(root_artifacts, root_directory);
+
+        // the graph is resolved level by level, everything on the same level in parallel;
+        // the results are processed in order, so they don't depend on which fetch finishes first
+        loop {
+            let level = resolution.next_level();
+            if level.is_empty() {
+                break;
             }
 
-            debug!("Resolving {}...", artifact);
-
-            let project = self
-                .build_effective_pom(&artifact)
-                .unwrap();
-
-            let _ = std::fs::create_dir_all(
-                root_directory.join(project.artifact_fqn.artifact_id.as_ref().unwrap()),
-            );
+            let projects = self.parallel_map(&level, |dependency| {
+                debug!("Resolving {}...", dependency.artifact_fqn);
+                self.effective_pom(&dependency.artifact_fqn)
+            });
+            let resolved = resolution.resolved(level, projects)?;
+
+            let to_extract = resolution.extractions(self, &resolved);
+            let downloads = self.parallel_map(&to_extract, |(artifact, handler, extract_path)| {
+                let _ = std::fs::create_dir_all(root_directory.join(artifact.artifact_id.as_ref().unwrap()));
+                self.download_package(artifact, Some(handler))?
+                    .extract_jar_file(extract_path)
+                    .map_err(|e| ResolverError::io(extract_path, e))
+            });
+            resolution.extracted(&to_extract, downloads)?;
 
-            let extract_path = PathBuf::from(
-                root_directory.join(project.artifact_fqn.with_packaging("jar").filename()),
-            );
+            resolution.add_dependencies(&resolved);
+        }
 
-            if !extract_path.exists() {
-                let package = self
-                    .try_download_package(&project.artifact_fqn)
-                    .unwrap();
+        resolution.classpath()
+    }
 
-                package.extract_jar_file(&extract_path).unwrap();
-            }
+    /// Calls `f` for every item using up to `concurrency` threads,
+    /// the results are in the same order as `items`.
+    fn parallel_map<T: Sync, R: Send>(&self, items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+        let threads = self.concurrency.min(items.len());
+        if threads <= 1 {
+            return items.iter().map(f).collect();
+        }
 
-            for dep in project
-                .dependencies
-                .values()
-                .filter(|dep| dep.scope.as_deref() == Some("compile"))
-            {
-                todo.push_back(dep.artifact_fqn.clone());
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let results = Mutex::new(items.iter().map(|_| None).collect::<Vec<_>>());
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock()[i] = Some(result);
+                });
             }
-        }
+        });
 
-        done.into_iter().map(|a| a.with_packaging("jar")).collect()
+        results
+            .into_inner()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect()
     }
 }
@@ -0,0 +1,218 @@
+//! A local Maven repository, e.g. `~/.m2/repository`.
+
+use crate::{Artifact, ArtifactSource, ResolverError, Validators};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// The name of the file where Maven tracks which remote repository each file came from.
+const REMOTE_REPOSITORIES: &str = "_remote.repositories";
+
+const REMOTE_REPOSITORIES_HEADER: &str = "#NOTE: This is a Maven Resolver internal implementation file, its format can be changed without prior notice.";
+
+/// A local repository with the standard layout, i.e.
+/// `<root>/<group as path>/<artifactId>/<version>/<artifactId>-<version>[-<classifier>].<extension>`.
+///
+/// Files downloaded into it are registered in `_remote.repositories`,
+/// same as Maven does, so the repository can be shared with Maven itself.
+/// The `ETag` and `Last-Modified` of the files that can change, i.e. snapshots
+/// and `maven-metadata.xml`, are kept in `<file>.<repository ID>.validators`.
+#[derive(Debug, Clone)]
+pub struct LocalRepository {
+    pub root: PathBuf,
+}
+
+impl LocalRepository {
+    pub fn new(root: &Path) -> Self {
+        LocalRepository {
+            root: root.to_owned(),
+        }
+    }
+
+    /// `~/.m2/repository`
+    pub fn user_default() -> Option<Self> {
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+        Some(Self::new(&PathBuf::from(home).join(".m2").join("repository")))
+    }
+
+    pub fn path_of(&self, id: &Artifact) -> Result<PathBuf, ResolverError> {
+        Ok(self.root.join(id.repository_path()?))
+    }
+
+    /// Stores a file downloaded from the remote repository `repository_id`.
+    pub fn install(
+        &self,
+        id: &Artifact,
+        repository_id: &str,
+        data: &[u8],
+    ) -> Result<PathBuf, ResolverError> {
+        let path = self.path_of(id)?;
+        let dir = path.parent().unwrap();
+        std::fs::create_dir_all(dir).map_err(|e| ResolverError::io(dir, e))?;
+
+        // write into a temporary file first, so that nobody sees a half-written file
+        let mut part = path.clone().into_os_string();
+        part.push(".part");
+        let part = PathBuf::from(part);
+        std::fs::write(&part, data).map_err(|e| ResolverError::io(&part, e))?;
+
+        self.install_file(id, repository_id, &part)
+    }
+
+    /// Same as [`LocalRepository::install`] for a file that's already on disk, e.g. a
+    /// temporary file it was streamed to. The file is moved, so it must be on the same
+    /// file system.
+    pub fn install_file(
+        &self,
+        id: &Artifact,
+        repository_id: &str,
+        file: &Path,
+    ) -> Result<PathBuf, ResolverError> {
+        let path = self.path_of(id)?;
+        let dir = path.parent().unwrap();
+        std::fs::create_dir_all(dir).map_err(|e| ResolverError::io(dir, e))?;
+        std::fs::rename(file, &path).map_err(|e| ResolverError::io(&path, e))?;
+
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        self.register(dir, &file_name, repository_id)?;
+
+        Ok(path)
+    }
+
+    /// Where `maven-metadata.xml` of `group_id:artifact_id`, or of one of its versions,
+    /// downloaded from `repository_id` is kept, same as Maven does.
+    pub fn metadata_path(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+        version: Option<&str>,
+        repository_id: &str,
+    ) -> PathBuf {
+        let mut path = self.root.join(group_id.replace('.', "/")).join(artifact_id);
+        if let Some(version) = version {
+            path.push(version);
+        }
+        path.join(format!("maven-metadata-{}.xml", repository_id))
+    }
+
+    /// Stores `maven-metadata.xml` downloaded from `repository_id`, see
+    /// [`LocalRepository::metadata_path`].
+    pub fn install_metadata(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+        version: Option<&str>,
+        repository_id: &str,
+        data: &[u8],
+    ) -> Result<PathBuf, ResolverError> {
+        let path = self.metadata_path(group_id, artifact_id, version, repository_id);
+        let dir = path.parent().unwrap();
+        std::fs::create_dir_all(dir).map_err(|e| ResolverError::io(dir, e))?;
+
+        // same as `install`, nobody sees a half-written file
+        let mut part = path.clone().into_os_string();
+        part.push(".part");
+        let part = PathBuf::from(part);
+        std::fs::write(&part, data).map_err(|e| ResolverError::io(&part, e))?;
+        std::fs::rename(&part, &path).map_err(|e| ResolverError::io(&path, e))?;
+
+        Ok(path)
+    }
+
+    /// The validators of the file at `path` when it was downloaded from `repository_id`,
+    /// to ask the repository whether it has changed since.
+    pub fn validators(&self, path: &Path, repository_id: &str) -> Option<Validators> {
+        let data = std::fs::read(validators_path(path, repository_id)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// Stores the validators of the file at `path` downloaded from `repository_id`,
+    /// or removes the old ones if the repository didn't send any.
+    pub fn set_validators(
+        &self,
+        path: &Path,
+        repository_id: &str,
+        validators: &Validators,
+    ) -> Result<(), ResolverError> {
+        let validators_path = validators_path(path, repository_id);
+        if *validators == Validators::default() {
+            return match std::fs::remove_file(&validators_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(ResolverError::io(&validators_path, e))
+                }
+                _ => Ok(()),
+            };
+        }
+
+        let data = serde_json::to_vec(validators).map_err(|e| {
+            ResolverError::invalid_data(&format!("can't serialize the validators of {}: {}", path.display(), e))
+        })?;
+        std::fs::write(&validators_path, data).map_err(|e| ResolverError::io(&validators_path, e))
+    }
+
+    /// The IDs of the remote repositories `id` is known to come from.
+    pub fn origins(&self, id: &Artifact) -> Result<Vec<String>, ResolverError> {
+        let path = self.path_of(id)?;
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        let entries = read_remote_repositories(path.parent().unwrap())?;
+        Ok(entries
+            .into_iter()
+            .filter(|(file, _)| *file == file_name)
+            .map(|(_, repository_id)| repository_id)
+            .collect())
+    }
+
+    fn register(&self, dir: &Path, file_name: &str, repository_id: &str) -> Result<(), ResolverError> {
+        let mut entries = read_remote_repositories(dir)?;
+        entries.insert((file_name.to_owned(), repository_id.to_owned()));
+
+        let mut text = format!("{}\n", REMOTE_REPOSITORIES_HEADER);
+        for (file, repository_id) in &entries {
+            text += &format!("{}>{}=\n", file, repository_id);
+        }
+
+        let path = dir.join(REMOTE_REPOSITORIES);
+        std::fs::write(&path, text).map_err(|e| ResolverError::io(&path, e))
+    }
+}
+
+impl ArtifactSource for LocalRepository {
+    fn find(&self, id: &Artifact) -> Option<PathBuf> {
+        self.path_of(id).ok().filter(|path| path.is_file())
+    }
+}
+
+fn validators_path(path: &Path, repository_id: &str) -> PathBuf {
+    let mut validators_path = path.as_os_str().to_owned();
+    validators_path.push(format!(".{}.validators", repository_id));
+    PathBuf::from(validators_path)
+}
+
+/// Marks a file as checked for updates just now, see [`crate::UpdatePolicy`].
+pub(crate) fn touch(path: &Path) -> Result<(), ResolverError> {
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+        .map_err(|e| ResolverError::io(path, e))
+}
+
+/// Reads `(file name, repository ID)` pairs from `_remote.repositories`.
+fn read_remote_repositories(dir: &Path) -> Result<BTreeSet<(String, String)>, ResolverError> {
+    let path = dir.join(REMOTE_REPOSITORIES);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeSet::new()),
+        Err(e) => return Err(ResolverError::io(&path, e)),
+    };
+
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let key = line.split_once('=').map(|(key, _)| key).unwrap_or(line);
+            let (file, repository_id) = key.split_once('>')?;
+            Some((file.to_owned(), repository_id.to_owned()))
+        })
+        .collect())
+}
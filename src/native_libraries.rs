@@ -0,0 +1,134 @@
+//! The native libraries of AARs, i.e. their `jni/<abi>/*.so` files, laid out
+//! per ABI the same way as in the `lib` directory of an APK.
+
+use crate::{Packaging, ResolverError};
+use log::{debug, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Two AARs with a library of the same name for the same ABI.
+///
+/// Only the first one is extracted, see [`NativeLibraries::collisions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    pub abi: String,
+    /// The file name of the library, e.g. `libc++_shared.so`.
+    pub name: String,
+    /// The AAR whose library was extracted.
+    pub kept: PathBuf,
+    /// The AAR whose library was left out.
+    pub ignored: PathBuf,
+}
+
+/// Extracts the native libraries of AARs into `<destination>/<abi>/<name>.so`.
+#[derive(Debug, Clone)]
+pub struct NativeLibraries {
+    pub destination: PathBuf,
+    /// The ABIs to extract, e.g. `arm64-v8a`, or all of them if not set.
+    pub abis: Option<BTreeSet<String>>,
+    /// The AAR of each extracted library, by ABI and name.
+    libraries: BTreeMap<(String, String), PathBuf>,
+    collisions: Vec<Collision>,
+}
+
+impl NativeLibraries {
+    pub fn new(destination: &Path) -> Self {
+        NativeLibraries {
+            destination: destination.to_owned(),
+            abis: None,
+            libraries: BTreeMap::new(),
+            collisions: vec![],
+        }
+    }
+
+    pub fn with_abis(self, abis: &[&str]) -> Self {
+        NativeLibraries {
+            abis: Some(abis.iter().map(|abi| abi.to_string()).collect()),
+            ..self
+        }
+    }
+
+    /// Extracts the libraries of `packaging` and returns their paths.
+    ///
+    /// Only AARs have native libraries, other packagings are skipped. A library
+    /// that was already extracted from another AAR is not overwritten, it's a
+    /// [`Collision`].
+    pub fn extract(&mut self, packaging: &Packaging) -> Result<Vec<PathBuf>, ResolverError> {
+        let Packaging::Aar(aar) = packaging else {
+            return Ok(vec![]);
+        };
+
+        let file = std::fs::File::open(aar).map_err(|e| ResolverError::io(aar, e))?;
+        let mut zip_file = zip::read::ZipArchive::new(std::io::BufReader::new(file))
+            .map_err(|e| ResolverError::io(aar, e))?;
+
+        let mut extracted = vec![];
+        for index in 0..zip_file.len() {
+            let mut entry = zip_file
+                .by_index(index)
+                .map_err(|e| ResolverError::io(aar, e))?;
+            let Some((abi, name)) = library_of(entry.name()) else {
+                continue;
+            };
+            if self.abis.as_ref().is_some_and(|abis| !abis.contains(abi)) {
+                continue;
+            }
+
+            let key = (abi.to_owned(), name.to_owned());
+            if let Some(kept) = self.libraries.get(&key) {
+                if kept != aar {
+                    warn!(
+                        "{} has {} for {}, already extracted from {}",
+                        aar.display(),
+                        name,
+                        abi,
+                        kept.display()
+                    );
+                    self.collisions.push(Collision {
+                        abi: key.0,
+                        name: key.1,
+                        kept: kept.clone(),
+                        ignored: aar.clone(),
+                    });
+                }
+                continue;
+            }
+
+            let dir = self.destination.join(abi);
+            std::fs::create_dir_all(&dir).map_err(|e| ResolverError::io(&dir, e))?;
+            let path = dir.join(name);
+            debug!("extracting {} of {} to {}", entry.name(), aar.display(), path.display());
+            std::fs::File::create(&path)
+                .and_then(|mut file| std::io::copy(&mut entry, &mut file))
+                .map_err(|e| ResolverError::io(&path, e))?;
+
+            self.libraries.insert(key, aar.clone());
+            extracted.push(path);
+        }
+
+        Ok(extracted)
+    }
+
+    /// The extracted libraries, as `(abi, name, aar)`.
+    pub fn libraries(&self) -> impl Iterator<Item = (&str, &str, &Path)> {
+        self.libraries
+            .iter()
+            .map(|((abi, name), aar)| (abi.as_str(), name.as_str(), aar.as_path()))
+    }
+
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collisions
+    }
+}
+
+/// The ABI and the file name of a `jni/<abi>/<name>.so` entry.
+fn library_of(entry: &str) -> Option<(&str, &str)> {
+    match entry.split('/').collect::<Vec<_>>()[..] {
+        ["jni", abi, name]
+            if !matches!(abi, "" | "." | "..") && name.ends_with(".so") && name != ".so" =>
+        {
+            Some((abi, name))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,177 @@
+//! Remembers which files are not available in which repositories.
+
+use crate::ResolverError;
+use log::{trace, warn};
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A cache of "not found" responses, keyed by the repository ID and the path
+/// of the file in the repository.
+///
+/// Entries are kept in memory and, if `directory` is set, in
+/// `<directory>/<repository ID>.txt` so they survive between runs. The file
+/// is compacted when it's loaded: the expired entries are dropped.
+#[derive(Debug)]
+pub struct NegativeCache {
+    pub directory: Option<PathBuf>,
+    pub expiry: Duration,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// (repository ID, path) -> when it was not found, in seconds since the Unix epoch
+    not_found: HashMap<(String, String), u64>,
+    /// Repositories whose entries were already loaded from disk.
+    loaded: HashSet<String>,
+}
+
+impl Default for NegativeCache {
+    fn default() -> Self {
+        NegativeCache {
+            directory: None,
+            expiry: Duration::from_secs(24 * 60 * 60),
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+}
+
+impl NegativeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_directory(self, directory: &Path) -> Self {
+        NegativeCache {
+            directory: Some(directory.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_expiry(self, expiry: Duration) -> Self {
+        NegativeCache { expiry, ..self }
+    }
+
+    pub fn is_missing(&self, repository_id: &str, path: &str) -> bool {
+        let entries = self.load(repository_id);
+
+        let key = (repository_id.to_owned(), path.to_owned());
+        match entries.not_found.get(&key) {
+            Some(not_found_at) => !self.is_expired(*not_found_at),
+            None => false,
+        }
+    }
+
+    pub fn mark_missing(&self, repository_id: &str, path: &str) {
+        // held while appending, so that the file isn't compacted meanwhile
+        let mut entries = self.load(repository_id);
+
+        trace!("{} is not in {}", path, repository_id);
+        let not_found_at = crate::unix_time();
+        entries
+            .not_found
+            .insert((repository_id.to_owned(), path.to_owned()), not_found_at);
+
+        if let Some(file) = self.file_of(repository_id) {
+            let result = std::fs::create_dir_all(file.parent().unwrap())
+                .and_then(|_| {
+                    std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&file)
+                })
+                .and_then(|mut f| writeln!(f, "{} {}", not_found_at, path));
+            if let Err(e) = result {
+                warn!("{}", ResolverError::io(&file, e).msg);
+            }
+        }
+    }
+
+    /// Forgets everything, both in memory and on disk.
+    pub fn clear(&self) -> Result<(), ResolverError> {
+        let mut entries = self.entries.lock();
+        entries.not_found.clear();
+        entries.loaded.clear();
+
+        if let Some(directory) = &self.directory {
+            match std::fs::remove_dir_all(directory) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(ResolverError::io(directory, e))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn file_of(&self, repository_id: &str) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        Some(directory.join(format!("{}.txt", repository_id)))
+    }
+
+    fn is_expired(&self, not_found_at: u64) -> bool {
+        crate::unix_time().saturating_sub(not_found_at) >= self.expiry.as_secs()
+    }
+
+    /// Locks the entries, after loading those of `repository_id` from disk
+    /// if that wasn't done yet.
+    fn load(&self, repository_id: &str) -> MutexGuard<'_, Entries> {
+        let mut entries = self.entries.lock();
+        if entries.loaded.contains(repository_id) {
+            return entries;
+        }
+
+        if let Some(file) = self.file_of(repository_id) {
+            if let Ok(text) = std::fs::read_to_string(&file) {
+                let mut lines = 0;
+                let mut live = HashMap::new();
+                for line in text.lines() {
+                    lines += 1;
+                    let Some((not_found_at, path)) = line.split_once(' ') else {
+                        continue;
+                    };
+                    let Ok(not_found_at) = not_found_at.parse::<u64>() else {
+                        continue;
+                    };
+                    if self.is_expired(not_found_at) {
+                        continue;
+                    }
+                    let entry: &mut u64 = live.entry(path.to_owned()).or_default();
+                    *entry = (*entry).max(not_found_at);
+                }
+
+                if live.len() < lines {
+                    self.compact(&file, &live);
+                }
+                for (path, not_found_at) in live {
+                    let entry = entries
+                        .not_found
+                        .entry((repository_id.to_owned(), path))
+                        .or_default();
+                    *entry = (*entry).max(not_found_at);
+                }
+            }
+        }
+
+        entries.loaded.insert(repository_id.to_owned());
+        entries
+    }
+
+    /// Rewrites `file` with the entries that haven't expired.
+    fn compact(&self, file: &Path, live: &HashMap<String, u64>) {
+        let mut text = String::new();
+        for (path, not_found_at) in live {
+            text.push_str(&format!("{} {}\n", not_found_at, path));
+        }
+
+        let tmp = file.with_extension("txt.tmp");
+        let result = std::fs::write(&tmp, text).and_then(|_| std::fs::rename(&tmp, file));
+        if let Err(e) = result {
+            warn!("{}", ResolverError::io(file, e).msg);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Downloaded packages and what extracting them means.
+
+use crate::ResolverError;
+use std::path::{Path, PathBuf};
+
+/// A downloaded package, i.e. the path of the file, by its extension.
+///
+/// Without a local repository the file is in a temporary directory,
+/// which is removed along with the resolver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packaging {
+    Aar(PathBuf),
+    Jar(PathBuf),
+    War(PathBuf),
+    Zip(PathBuf),
+    TarGz(PathBuf),
+    /// A Kotlin/Native library.
+    Klib(PathBuf),
+    /// Nothing but the POM, e.g. a BOM or a parent project.
+    Pom(PathBuf),
+    /// A native library, e.g. `.so`.
+    Native(PathBuf),
+    /// Anything else, it can't be extracted.
+    Other { extension: String, path: PathBuf },
+}
+
+impl Packaging {
+    pub fn new(extension: &str, path: PathBuf) -> Self {
+        match extension {
+            "aar" => Packaging::Aar(path),
+            "jar" => Packaging::Jar(path),
+            "war" => Packaging::War(path),
+            "zip" => Packaging::Zip(path),
+            "tar.gz" | "tgz" => Packaging::TarGz(path),
+            "klib" => Packaging::Klib(path),
+            "pom" => Packaging::Pom(path),
+            "so" | "dylib" | "dll" | "a" => Packaging::Native(path),
+            _ => Packaging::Other {
+                extension: extension.to_owned(),
+                path,
+            },
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Aar(path)
+            | Self::Jar(path)
+            | Self::War(path)
+            | Self::Zip(path)
+            | Self::TarGz(path)
+            | Self::Klib(path)
+            | Self::Pom(path)
+            | Self::Native(path)
+            | Self::Other { path, .. } => path,
+        }
+    }
+
+    /// Extracts the package to `destination`:
+    ///
+    /// - JARs, KLIBs and native libraries are copied to the `destination` file,
+    /// - for AARs, that's their `classes.jar`,
+    /// - WARs, ZIPs and tarballs are unpacked into the `destination` directory,
+    /// - there's nothing to extract from a POM.
+    ///
+    /// Other packages can't be extracted.
+    pub fn extract(&self, destination: &Path) -> Result<(), ResolverError> {
+        match self {
+            Self::Aar(_) | Self::Jar(_) => self
+                .extract_jar_file(destination)
+                .map_err(|e| ResolverError::io(destination, e)),
+            Self::Klib(path) | Self::Native(path) => std::fs::copy(path, destination)
+                .map(|_| ())
+                .map_err(|e| ResolverError::io(destination, e)),
+            Self::War(path) | Self::Zip(path) => {
+                let file = std::fs::File::open(path).map_err(|e| ResolverError::io(path, e))?;
+                zip::read::ZipArchive::new(std::io::BufReader::new(file))
+                    .and_then(|mut zip_file| zip_file.extract(destination))
+                    .map_err(|e| ResolverError::io(path, e))
+            }
+            Self::TarGz(path) => {
+                let file = std::fs::File::open(path).map_err(|e| ResolverError::io(path, e))?;
+                tar::Archive::new(flate2::read::GzDecoder::new(std::io::BufReader::new(file)))
+                    .unpack(destination)
+                    .map_err(|e| ResolverError::io(path, e))
+            }
+            Self::Pom(_) => Ok(()),
+            Self::Other { extension, path } => Err(ResolverError::invalid_data(&format!(
+                "{}: don't know how to extract a .{} package",
+                path.display(),
+                extension
+            ))),
+        }
+    }
+
+    /// Extracts the JAR that goes on the classpath, i.e. the JAR itself
+    /// or the `classes.jar` of an AAR, to the `location` file.
+    pub fn extract_jar_file(
+        &self,
+        location: &std::path::Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Aar(path) => {
+                let file = std::io::BufReader::new(std::fs::File::open(path)?);
+                let mut zip_file = zip::read::ZipArchive::new(file)?;
+                let mut classes = zip_file.by_name("classes.jar")?;
+                std::io::copy(&mut classes, &mut std::fs::File::create(location)?)?;
+                Ok(())
+            }
+            Self::Jar(path) => {
+                std::fs::copy(path, location)?;
+                Ok(())
+            }
+            other => Err(format!("{} is not a JAR or an AAR", other.path().display()).into()),
+        }
+    }
+}
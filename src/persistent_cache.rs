@@ -0,0 +1,154 @@
+//! An on-disk cache of parsed projects, shared between processes.
+
+use crate::checksum::Digests;
+use crate::{Artifact, Project, ResolverError, UpdatePolicy, Validators};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A cached project, along with what's needed to revalidate it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// When the POM was fetched or last revalidated, in seconds since the Unix epoch.
+    pub fetched_at: u64,
+    #[serde(default)]
+    pub validators: Validators,
+    pub project: Project,
+    /// The digests of the POM, to check the project against the verification metadata.
+    #[serde(default)]
+    pub digests: Option<Digests>,
+    /// The fingerprint of the key that signed the POM, if its signature was verified.
+    #[serde(default)]
+    pub signer: Option<String>,
+}
+
+impl CacheEntry {
+    /// Whether the entry can be used without asking the repository, i.e. it's a release
+    /// or a snapshot that doesn't have to be updated yet.
+    pub fn is_fresh(&self, id: &Artifact, update_policy: UpdatePolicy) -> bool {
+        !id.is_snapshot() || !update_policy.is_stale(self.fetched_at, crate::unix_time())
+    }
+}
+
+/// Parsed projects stored as JSON files in
+/// `<root>/<repository ID>/<group as path>/<artifactId>/<version>.json`.
+///
+/// Release POMs never change, so they never expire. Snapshots are
+/// re-fetched according to the repository's update policy, using
+/// conditional requests if the repository supports them.
+#[derive(Debug, Clone)]
+pub struct PersistentCache {
+    pub root: PathBuf,
+}
+
+impl PersistentCache {
+    pub fn new(root: &Path) -> Self {
+        PersistentCache {
+            root: root.to_owned(),
+        }
+    }
+
+    fn path_of(&self, repository_id: &str, id: &Artifact) -> Option<PathBuf> {
+        Some(
+            self.root
+                .join(repository_id)
+                .join(id.group_id.as_ref()?.replace('.', "/"))
+                .join(id.artifact_id.as_ref()?)
+                .join(format!("{}.json", id.version_cleaned()?)),
+        )
+    }
+
+    /// Returns the cached project unless it's a snapshot that has to be updated.
+    pub fn get(
+        &self,
+        repository_id: &str,
+        id: &Artifact,
+        update_policy: UpdatePolicy,
+    ) -> Option<Project> {
+        let entry = self.entry(repository_id, id)?;
+        if !entry.is_fresh(id, update_policy) {
+            debug!("{} has expired in the persistent cache", id);
+            return None;
+        }
+        Some(entry.project)
+    }
+
+    /// Returns the cached entry, fresh or not.
+    pub fn entry(&self, repository_id: &str, id: &Artifact) -> Option<CacheEntry> {
+        let path = self.path_of(repository_id, id)?;
+        let data = std::fs::read(&path).ok()?;
+
+        match serde_json::from_slice(&data) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("ignoring a broken cache entry {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn put(
+        &self,
+        repository_id: &str,
+        id: &Artifact,
+        project: &Project,
+        validators: &Validators,
+    ) -> Result<(), ResolverError> {
+        let entry = CacheEntry {
+            fetched_at: crate::unix_time(),
+            validators: validators.clone(),
+            project: project.clone(),
+            digests: None,
+            signer: None,
+        };
+        self.put_entry(repository_id, id, &entry)
+    }
+
+    /// Same as [`PersistentCache::put`] with the whole entry, e.g. with the digests of the POM.
+    pub fn put_entry(
+        &self,
+        repository_id: &str,
+        id: &Artifact,
+        entry: &CacheEntry,
+    ) -> Result<(), ResolverError> {
+        let Some(path) = self.path_of(repository_id, id) else {
+            return Ok(());
+        };
+
+        let data = serde_json::to_vec(entry)
+            .map_err(|e| ResolverError::invalid_data(&format!("can't serialize {}: {}", id, e)))?;
+
+        let dir = path.parent().unwrap();
+        std::fs::create_dir_all(dir).map_err(|e| ResolverError::io(dir, e))?;
+
+        // other processes may be reading the same entry
+        let mut part = path.clone().into_os_string();
+        part.push(".part");
+        let part = PathBuf::from(part);
+        std::fs::write(&part, data).map_err(|e| ResolverError::io(&part, e))?;
+        std::fs::rename(&part, &path).map_err(|e| ResolverError::io(&path, e))
+    }
+}
+
+/// Serializes dependency maps as lists, JSON doesn't support non-string keys.
+pub(crate) mod dependencies_as_list {
+    use crate::{Dependency, DependencyKey};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        dependencies: &HashMap<DependencyKey, Dependency>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut list = dependencies.values().collect::<Vec<_>>();
+        list.sort_by_key(|dep| dep.artifact_fqn.to_string());
+        list.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<DependencyKey, Dependency>, D::Error> {
+        let list = Vec::<Dependency>::deserialize(deserializer)?;
+        Ok(list.into_iter().map(|dep| (dep.get_key(), dep)).collect())
+    }
+}
@@ -0,0 +1,373 @@
+//! [Prefab](https://google.github.io/prefab/) packages, i.e. the C/C++ headers
+//! and libraries that AARs ship under `prefab/`, for native builds to link with.
+
+use crate::{Packaging, ResolverError};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// The `prefab.json` of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageMetadata {
+    pub schema_version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The names of the Prefab packages this one depends on.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+/// The `module.json` of a module.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleMetadata {
+    /// The libraries the users of the module link with too, e.g. `-llog`.
+    #[serde(default)]
+    pub export_libraries: Vec<String>,
+    /// The file name of the library without its extension, `lib<module>` if not set.
+    #[serde(default)]
+    pub library_name: Option<String>,
+    /// The Android specific overrides of the above.
+    #[serde(default)]
+    pub android: Option<AndroidMetadata>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AndroidMetadata {
+    #[serde(default)]
+    pub export_libraries: Option<Vec<String>>,
+    #[serde(default)]
+    pub library_name: Option<String>,
+}
+
+/// The `abi.json` of a library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbiMetadata {
+    /// e.g. `arm64-v8a`.
+    pub abi: String,
+    /// The minimum Android API level.
+    pub api: u32,
+    /// The major version of the NDK the library was built with.
+    pub ndk: u32,
+    /// e.g. `c++_shared`, `c++_static` or `none`.
+    pub stl: String,
+    #[serde(rename = "static", default)]
+    pub is_static: bool,
+}
+
+/// An extracted Prefab package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefabPackage {
+    pub metadata: PackageMetadata,
+    /// Where the package was extracted, the same layout as `prefab/` in the AAR.
+    pub path: PathBuf,
+    pub modules: Vec<PrefabModule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefabModule {
+    pub name: String,
+    pub metadata: ModuleMetadata,
+    /// The headers, if the module has any.
+    pub include_dir: Option<PathBuf>,
+    /// The builds of the library, one per ABI, STL, etc. None for header-only modules.
+    pub libraries: Vec<PrefabLibrary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefabLibrary {
+    pub abi: AbiMetadata,
+    /// The library file, e.g. `libs/android.arm64-v8a/libgame-activity.so`.
+    pub path: PathBuf,
+    /// The headers specific to this build, e.g. `libs/android.arm64-v8a/include`.
+    pub include_dir: Option<PathBuf>,
+}
+
+impl PrefabPackage {
+    /// Extracts the Prefab package of `packaging` into `<destination>/<name>`.
+    ///
+    /// Returns `None` if `packaging` is not an AAR or has no `prefab/prefab.json`.
+    pub fn extract(packaging: &Packaging, destination: &Path) -> Result<Option<Self>, ResolverError> {
+        let Packaging::Aar(aar) = packaging else {
+            return Ok(None);
+        };
+
+        let file = std::fs::File::open(aar).map_err(|e| ResolverError::io(aar, e))?;
+        let mut zip_file = zip::read::ZipArchive::new(std::io::BufReader::new(file))
+            .map_err(|e| ResolverError::io(aar, e))?;
+
+        let metadata: PackageMetadata = match zip_file.by_name("prefab/prefab.json") {
+            Ok(entry) => serde_json::from_reader(entry).map_err(|e| {
+                ResolverError::invalid_data(&format!("{}: prefab/prefab.json: {}", aar.display(), e))
+            })?,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(ResolverError::io(aar, e)),
+        };
+
+        // the name comes from the AAR, it must not lead outside the destination
+        let mut components = Path::new(&metadata.name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(ResolverError::invalid_data(&format!(
+                "{}: invalid Prefab package name {:?}",
+                aar.display(),
+                metadata.name
+            )));
+        }
+
+        let path = destination.join(&metadata.name);
+        debug!("extracting the Prefab package of {} to {}", aar.display(), path.display());
+        for index in 0..zip_file.len() {
+            let mut entry = zip_file
+                .by_index(index)
+                .map_err(|e| ResolverError::io(aar, e))?;
+            // never outside the destination
+            let Some(name) = entry.enclosed_name() else {
+                continue;
+            };
+            let Ok(name) = name.strip_prefix("prefab") else {
+                continue;
+            };
+            if entry.is_dir() || name.as_os_str().is_empty() {
+                continue;
+            }
+
+            let target = path.join(name);
+            let dir = target.parent().unwrap();
+            std::fs::create_dir_all(dir).map_err(|e| ResolverError::io(dir, e))?;
+            std::fs::File::create(&target)
+                .and_then(|mut file| std::io::copy(&mut entry, &mut file))
+                .map_err(|e| ResolverError::io(&target, e))?;
+        }
+
+        let modules = read_modules(&path.join("modules"))?;
+        Ok(Some(PrefabPackage {
+            metadata,
+            path,
+            modules,
+        }))
+    }
+
+    /// The include directories of all the modules, and those of their libraries
+    /// built for `abi`, and for `stl` if set, for e.g. `cc::Build::includes`.
+    pub fn include_dirs(&self, abi: &str, stl: Option<&str>) -> Vec<&Path> {
+        let module_dirs = self
+            .modules
+            .iter()
+            .filter_map(|module| module.include_dir.as_deref());
+        let library_dirs = self
+            .libraries(abi, stl)
+            .into_iter()
+            .filter_map(|(_, library)| library.include_dir.as_deref());
+        module_dirs.chain(library_dirs).collect()
+    }
+
+    /// The libraries built for `abi`, and for `stl` if set.
+    pub fn libraries(&self, abi: &str, stl: Option<&str>) -> Vec<(&PrefabModule, &PrefabLibrary)> {
+        self.modules
+            .iter()
+            .flat_map(|module| {
+                module
+                    .libraries_for(abi, stl)
+                    .into_iter()
+                    .map(move |library| (module, library))
+            })
+            .collect()
+    }
+
+    /// The directories of the libraries built for `abi`, and for `stl` if set.
+    pub fn link_search_paths(&self, abi: &str, stl: Option<&str>) -> Vec<&Path> {
+        let mut seen = HashSet::new();
+        self.libraries(abi, stl)
+            .into_iter()
+            .filter_map(|(_, library)| library.path.parent())
+            .filter(|path| seen.insert(*path))
+            .collect()
+    }
+
+    /// The `cargo:` instructions for a `build.rs` to link with all the libraries
+    /// built for `abi`, and for `stl` if set, see [`cargo_link_instructions`].
+    pub fn cargo_link_instructions(&self, abi: &str, stl: Option<&str>) -> Vec<String> {
+        cargo_link_instructions(std::slice::from_ref(self), abi, stl)
+    }
+
+    fn module(&self, name: &str) -> Option<&PrefabModule> {
+        self.modules.iter().find(|module| module.name == name)
+    }
+}
+
+/// The `cargo:` instructions for a `build.rs` to link with all the libraries of
+/// `packages` built for `abi`, and for `stl` if set.
+///
+/// The libraries their modules export are linked too, transitively: `-l` flags,
+/// `:<module>` of the same package, and `//<package>:<module>` of another one of
+/// `packages`.
+pub fn cargo_link_instructions(packages: &[PrefabPackage], abi: &str, stl: Option<&str>) -> Vec<String> {
+    let mut search_paths = vec![];
+    let mut libs = vec![];
+
+    let mut link = |library: &PrefabLibrary| {
+        let dir = library.path.parent().unwrap_or(Path::new(""));
+        let search_path = format!("cargo:rustc-link-search=native={}", dir.display());
+        if !search_paths.contains(&search_path) {
+            search_paths.push(search_path);
+        }
+
+        let kind = if library.abi.is_static { "static" } else { "dylib" };
+        let Some(name) = library.path.file_stem().and_then(|name| name.to_str()) else {
+            return;
+        };
+        let lib = format!(
+            "cargo:rustc-link-lib={}={}",
+            kind,
+            name.strip_prefix("lib").unwrap_or(name)
+        );
+        if !libs.contains(&lib) {
+            libs.push(lib);
+        }
+    };
+
+    // the modules to link, the exported ones after those that export them
+    let mut queue: std::collections::VecDeque<_> = packages
+        .iter()
+        .flat_map(|package| {
+            package
+                .libraries(abi, stl)
+                .into_iter()
+                .map(move |(module, _)| (package, module))
+        })
+        .collect();
+    let mut done = vec![];
+    let mut flags = vec![];
+    while let Some((package, module)) = queue.pop_front() {
+        if done.contains(&(&package.metadata.name, &module.name)) {
+            continue;
+        }
+        done.push((&package.metadata.name, &module.name));
+
+        module.libraries_for(abi, stl).into_iter().for_each(&mut link);
+
+        for export in module.export_libraries() {
+            let exported = if let Some(name) = export.strip_prefix("//") {
+                name.split_once(':').and_then(|(package_name, module_name)| {
+                    let package = packages
+                        .iter()
+                        .find(|package| package.metadata.name == package_name)?;
+                    Some((package, package.module(module_name)?))
+                })
+            } else if let Some(module_name) = export.strip_prefix(':') {
+                package.module(module_name).map(|module| (package, module))
+            } else {
+                let flag = match export.strip_prefix("-l") {
+                    Some(name) => format!("cargo:rustc-link-lib={}", name),
+                    None => format!("cargo:rustc-link-arg={}", export),
+                };
+                if !flags.contains(&flag) {
+                    flags.push(flag);
+                }
+                continue;
+            };
+
+            match exported {
+                Some(exported) => queue.push_back(exported),
+                None => warn!(
+                    "{} exports {}, which is not among the Prefab packages",
+                    module.name, export
+                ),
+            }
+        }
+    }
+
+    search_paths.into_iter().chain(libs).chain(flags).collect()
+}
+
+impl PrefabModule {
+    /// The file name of the library without its extension, e.g. `libgame-activity`.
+    pub fn library_name(&self) -> String {
+        self.metadata
+            .android
+            .as_ref()
+            .and_then(|android| android.library_name.clone())
+            .or_else(|| self.metadata.library_name.clone())
+            .unwrap_or_else(|| format!("lib{}", self.name))
+    }
+
+    /// The builds of the library for `abi`, and for `stl` if set.
+    pub fn libraries_for(&self, abi: &str, stl: Option<&str>) -> Vec<&PrefabLibrary> {
+        self.libraries
+            .iter()
+            .filter(|library| library.abi.abi == abi && stl.is_none_or(|stl| library.abi.stl == stl))
+            .collect()
+    }
+
+    /// The libraries the users of the module link with too, e.g. `-llog` or
+    /// `:<module>` of the same package.
+    pub fn export_libraries(&self) -> &[String] {
+        self.metadata
+            .android
+            .as_ref()
+            .and_then(|android| android.export_libraries.as_deref())
+            .unwrap_or(&self.metadata.export_libraries)
+    }
+}
+
+fn read_modules(dir: &Path) -> Result<Vec<PrefabModule>, ResolverError> {
+    let mut modules = vec![];
+    for module_dir in read_dir(dir)? {
+        let name = module_dir.file_name().unwrap().to_string_lossy().into_owned();
+        let metadata: ModuleMetadata = read_json(&module_dir.join("module.json"))?;
+        let include_dir = Some(module_dir.join("include")).filter(|dir| dir.is_dir());
+
+        let mut module = PrefabModule {
+            name,
+            metadata,
+            include_dir,
+            libraries: vec![],
+        };
+
+        let library_name = module.library_name();
+        for library_dir in read_dir(&module_dir.join("libs"))? {
+            let abi: AbiMetadata = read_json(&library_dir.join("abi.json"))?;
+            let extension = if abi.is_static { "a" } else { "so" };
+            let path = library_dir.join(format!("{}.{}", library_name, extension));
+            if !path.is_file() {
+                return Err(ResolverError::invalid_data(&format!(
+                    "{} is missing",
+                    path.display()
+                )));
+            }
+            let include_dir = Some(library_dir.join("include")).filter(|dir| dir.is_dir());
+            module.libraries.push(PrefabLibrary {
+                abi,
+                path,
+                include_dir,
+            });
+        }
+
+        modules.push(module);
+    }
+    Ok(modules)
+}
+
+/// The sub-directories of `dir` sorted by name, none if it doesn't exist.
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, ResolverError> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut dirs = vec![];
+    for entry in std::fs::read_dir(dir).map_err(|e| ResolverError::io(dir, e))? {
+        let path = entry.map_err(|e| ResolverError::io(dir, e))?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, ResolverError> {
+    let data = std::fs::read(path).map_err(|e| ResolverError::io(path, e))?;
+    serde_json::from_slice(&data)
+        .map_err(|e| ResolverError::invalid_data(&format!("{}: {}", path.display(), e)))
+}
@@ -0,0 +1,116 @@
+//! Declares which artifacts a repository may serve, like Gradle's
+//! `content { includeGroupByRegex("androidx.*") }`.
+
+use crate::{Artifact, ResolverError};
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    group: Regex,
+    artifact: Option<Regex>,
+}
+
+impl Pattern {
+    fn exact(group: &str, artifact: Option<&str>) -> Self {
+        let exact = |s: &str| Regex::new(&format!("^{}$", regex::escape(s))).unwrap();
+        Pattern {
+            group: exact(group),
+            artifact: artifact.map(exact),
+        }
+    }
+
+    fn regex(group: &str, artifact: Option<&str>) -> Result<Self, ResolverError> {
+        let anchored = |s: &str| {
+            Regex::new(&format!("^(?:{})$", s)).map_err(|e| {
+                ResolverError::invalid_data(&format!("invalid content pattern {}: {}", s, e))
+            })
+        };
+        Ok(Pattern {
+            group: anchored(group)?,
+            artifact: artifact.map(anchored).transpose()?,
+        })
+    }
+
+    fn matches(&self, group_id: &str, artifact_id: &str) -> bool {
+        self.group.is_match(group_id)
+            && self
+                .artifact
+                .as_ref()
+                .is_none_or(|artifact| artifact.is_match(artifact_id))
+    }
+}
+
+/// Include and exclude patterns for groups and artifacts.
+///
+/// With no includes, everything that isn't excluded matches. Otherwise an
+/// artifact has to match at least one include and no excludes.
+#[derive(Debug, Clone, Default)]
+pub struct RepositoryContent {
+    includes: Vec<Pattern>,
+    excludes: Vec<Pattern>,
+}
+
+impl RepositoryContent {
+    pub fn include_group(mut self, group_id: &str) -> Self {
+        self.includes.push(Pattern::exact(group_id, None));
+        self
+    }
+
+    pub fn include_group_by_regex(mut self, group_regex: &str) -> Result<Self, ResolverError> {
+        self.includes.push(Pattern::regex(group_regex, None)?);
+        Ok(self)
+    }
+
+    pub fn include_module(mut self, group_id: &str, artifact_id: &str) -> Self {
+        self.includes.push(Pattern::exact(group_id, Some(artifact_id)));
+        self
+    }
+
+    pub fn include_module_by_regex(
+        mut self,
+        group_regex: &str,
+        artifact_regex: &str,
+    ) -> Result<Self, ResolverError> {
+        self.includes.push(Pattern::regex(group_regex, Some(artifact_regex))?);
+        Ok(self)
+    }
+
+    pub fn exclude_group(mut self, group_id: &str) -> Self {
+        self.excludes.push(Pattern::exact(group_id, None));
+        self
+    }
+
+    pub fn exclude_group_by_regex(mut self, group_regex: &str) -> Result<Self, ResolverError> {
+        self.excludes.push(Pattern::regex(group_regex, None)?);
+        Ok(self)
+    }
+
+    pub fn exclude_module(mut self, group_id: &str, artifact_id: &str) -> Self {
+        self.excludes.push(Pattern::exact(group_id, Some(artifact_id)));
+        self
+    }
+
+    pub fn exclude_module_by_regex(
+        mut self,
+        group_regex: &str,
+        artifact_regex: &str,
+    ) -> Result<Self, ResolverError> {
+        self.excludes.push(Pattern::regex(group_regex, Some(artifact_regex))?);
+        Ok(self)
+    }
+
+    pub fn matches(&self, id: &Artifact) -> bool {
+        let group_id = id.group_id.as_deref().unwrap_or_default();
+        let artifact_id = id.artifact_id.as_deref().unwrap_or_default();
+
+        (self.includes.is_empty()
+            || self
+                .includes
+                .iter()
+                .any(|pattern| pattern.matches(group_id, artifact_id)))
+            && !self
+                .excludes
+                .iter()
+                .any(|pattern| pattern.matches(group_id, artifact_id))
+    }
+}
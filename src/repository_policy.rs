@@ -0,0 +1,145 @@
+//! Maven's `<releases>` and `<snapshots>` repository policies, and how hard we try
+//! to fetch from a repository.
+
+use crate::ChecksumPolicy;
+use std::time::Duration;
+
+/// How often cached metadata is checked for updates, same as Maven's `<updatePolicy>`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdatePolicy {
+    /// Every time.
+    Always,
+    /// Once a day (UTC).
+    #[default]
+    Daily,
+    /// Every N minutes.
+    Interval(u64),
+    /// Only if it's missing.
+    Never,
+}
+
+impl UpdatePolicy {
+    /// Parses `always`, `daily`, `interval:N` or `never`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "always" => Some(UpdatePolicy::Always),
+            "daily" => Some(UpdatePolicy::Daily),
+            "never" => Some(UpdatePolicy::Never),
+            s => s
+                .strip_prefix("interval:")
+                .and_then(|minutes| minutes.trim().parse().ok())
+                .map(UpdatePolicy::Interval),
+        }
+    }
+
+    /// Whether something fetched at `fetched_at` should be checked for updates at `now`
+    /// (both in seconds since the Unix epoch).
+    pub fn is_stale(&self, fetched_at: u64, now: u64) -> bool {
+        const DAY: u64 = 24 * 60 * 60;
+
+        match self {
+            UpdatePolicy::Always => true,
+            UpdatePolicy::Daily => fetched_at / DAY < now / DAY,
+            UpdatePolicy::Interval(minutes) => {
+                now.saturating_sub(fetched_at) >= minutes.saturating_mul(60)
+            }
+            UpdatePolicy::Never => false,
+        }
+    }
+
+    /// How many minutes there are between the checks, roughly for [`UpdatePolicy::Daily`].
+    fn minutes(&self) -> u64 {
+        match self {
+            UpdatePolicy::Always => 0,
+            UpdatePolicy::Daily => 24 * 60,
+            UpdatePolicy::Interval(minutes) => *minutes,
+            UpdatePolicy::Never => u64::MAX,
+        }
+    }
+}
+
+/// The policy for either releases or snapshots of a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RepositoryPolicy {
+    /// Whether the repository is asked for this kind of artifacts at all.
+    pub enabled: bool,
+    pub update_policy: UpdatePolicy,
+    pub checksum_policy: ChecksumPolicy,
+}
+
+impl Default for RepositoryPolicy {
+    fn default() -> Self {
+        RepositoryPolicy {
+            enabled: true,
+            update_policy: UpdatePolicy::default(),
+            checksum_policy: ChecksumPolicy::default(),
+        }
+    }
+}
+
+impl RepositoryPolicy {
+    pub fn disabled() -> Self {
+        RepositoryPolicy {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// The policy of a mirror that serves repositories with either policy, same as Maven:
+    /// it's enabled if either is, and then checks for updates as often and the
+    /// checksums as strictly as the stricter of the enabled ones.
+    pub fn merge(self, other: RepositoryPolicy) -> RepositoryPolicy {
+        match (self.enabled, other.enabled) {
+            (true, true) => RepositoryPolicy {
+                enabled: true,
+                update_policy: match self.update_policy.minutes() <= other.update_policy.minutes() {
+                    true => self.update_policy,
+                    false => other.update_policy,
+                },
+                checksum_policy: self.checksum_policy.stricter(other.checksum_policy),
+            },
+            (false, true) => other,
+            _ => self,
+        }
+    }
+}
+
+/// Timeouts and retries of the requests to a repository.
+///
+/// Only transient failures (timeouts, dropped connections, 5xx and 429 responses) are retried,
+/// with a jittered exponential backoff between the attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FetchPolicy {
+    pub connect_timeout: Duration,
+    /// How long to wait for the next chunk of the response.
+    pub read_timeout: Duration,
+    /// How many times a request is retried, i.e. `0` for no retries.
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled for each next one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        FetchPolicy {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl FetchPolicy {
+    /// The delay before the retry number `retry` (starting with 0): somewhere between
+    /// a half and the whole of `initial_backoff * 2^retry`, but no longer than `max_backoff`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+    }
+}
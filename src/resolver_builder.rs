@@ -0,0 +1,187 @@
+//! A builder for [`Resolver`], for everything that can't be changed afterwards.
+
+use crate::*;
+
+/// Builds a [`Resolver`] with custom fetchers, parsers, caches and policies.
+///
+/// Without the `default-impl` feature, a [`UrlFetcher`] and a [`PomParser`] must be set.
+#[derive(Default)]
+pub struct ResolverBuilder {
+    repositories: Vec<Arc<Repository>>,
+    url_fetcher: Option<Box<dyn UrlFetcher>>,
+    pom_parser: Option<Box<dyn PomParser>>,
+    #[cfg(feature = "pgp")]
+    keyring: Keyring,
+    #[cfg(feature = "pgp")]
+    signature_policy: SignaturePolicy,
+    verification_metadata: Option<VerificationMetadata>,
+    verification_mode: Option<VerificationMode>,
+    offline: bool,
+    local_repository: Option<LocalRepository>,
+    artifact_sources: Vec<Box<dyn ArtifactSource>>,
+    persistent_cache: Option<PersistentCache>,
+    negative_cache: NegativeCache,
+    force_refresh: bool,
+    credential_providers: Vec<Box<dyn CredentialProvider>>,
+    concurrency: Option<usize>,
+    artifact_handlers: ArtifactHandlers,
+}
+
+impl ResolverBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_repositories(mut self, repositories: &[Arc<Repository>]) -> Self {
+        self.repositories.extend_from_slice(repositories);
+        self
+    }
+
+    pub fn with_repository(mut self, repository: Repository) -> Self {
+        self.repositories.push(Arc::new(repository));
+        self
+    }
+
+    pub fn with_url_fetcher(self, url_fetcher: impl UrlFetcher + 'static) -> Self {
+        ResolverBuilder {
+            url_fetcher: Some(Box::new(url_fetcher)),
+            ..self
+        }
+    }
+
+    pub fn with_pom_parser(self, pom_parser: impl PomParser + 'static) -> Self {
+        ResolverBuilder {
+            pom_parser: Some(Box::new(pom_parser)),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "pgp")]
+    pub fn with_keyring(self, keyring: Keyring) -> Self {
+        ResolverBuilder { keyring, ..self }
+    }
+
+    #[cfg(feature = "pgp")]
+    pub fn with_signature_policy(self, signature_policy: SignaturePolicy) -> Self {
+        ResolverBuilder {
+            signature_policy,
+            ..self
+        }
+    }
+
+    pub fn with_verification_metadata(
+        self,
+        verification_metadata: VerificationMetadata,
+        verification_mode: VerificationMode,
+    ) -> Self {
+        ResolverBuilder {
+            verification_metadata: Some(verification_metadata),
+            verification_mode: Some(verification_mode),
+            ..self
+        }
+    }
+
+    pub fn with_offline(self, offline: bool) -> Self {
+        ResolverBuilder { offline, ..self }
+    }
+
+    pub fn with_local_repository(self, local_repository: LocalRepository) -> Self {
+        ResolverBuilder {
+            local_repository: Some(local_repository),
+            ..self
+        }
+    }
+
+    pub fn with_artifact_source(mut self, artifact_source: impl ArtifactSource + 'static) -> Self {
+        self.artifact_sources.push(Box::new(artifact_source));
+        self
+    }
+
+    pub fn with_persistent_cache(self, persistent_cache: PersistentCache) -> Self {
+        ResolverBuilder {
+            persistent_cache: Some(persistent_cache),
+            ..self
+        }
+    }
+
+    pub fn with_negative_cache(self, negative_cache: NegativeCache) -> Self {
+        ResolverBuilder {
+            negative_cache,
+            ..self
+        }
+    }
+
+    pub fn with_force_refresh(self, force_refresh: bool) -> Self {
+        ResolverBuilder {
+            force_refresh,
+            ..self
+        }
+    }
+
+    pub fn with_credential_provider(
+        mut self,
+        credential_provider: impl CredentialProvider + 'static,
+    ) -> Self {
+        self.credential_providers.push(Box::new(credential_provider));
+        self
+    }
+
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        ResolverBuilder {
+            concurrency: Some(concurrency),
+            ..self
+        }
+    }
+
+    /// Adds a handler for a dependency type or a packaging, or replaces Maven's default one.
+    pub fn with_artifact_handler(mut self, artifact_handler: ArtifactHandler) -> Self {
+        self.artifact_handlers.register(artifact_handler);
+        self
+    }
+
+    pub fn build(self) -> Result<Resolver, ResolverError> {
+        #[cfg(feature = "default-impl")]
+        let url_fetcher = self
+            .url_fetcher
+            .unwrap_or_else(|| Box::new(default_impl::DefaultUrlFetcher::default()));
+        #[cfg(not(feature = "default-impl"))]
+        let url_fetcher = self.url_fetcher.ok_or_else(|| {
+            ResolverError::invalid_data("no UrlFetcher set and the default-impl feature is disabled")
+        })?;
+
+        #[cfg(feature = "default-impl")]
+        let pom_parser = self
+            .pom_parser
+            .unwrap_or_else(|| Box::new(default_impl::DefaultPomParser {}));
+        #[cfg(not(feature = "default-impl"))]
+        let pom_parser = self.pom_parser.ok_or_else(|| {
+            ResolverError::invalid_data("no PomParser set and the default-impl feature is disabled")
+        })?;
+
+        Ok(Resolver {
+            repositories: self.repositories,
+            project_cache: Mutex::new(HashMap::new()),
+            #[cfg(feature = "pgp")]
+            keyring: self.keyring,
+            #[cfg(feature = "pgp")]
+            signature_policy: self.signature_policy,
+            #[cfg(feature = "pgp")]
+            signers: Mutex::new(HashMap::new()),
+            verification_metadata: Mutex::new(self.verification_metadata),
+            verification_mode: self.verification_mode.unwrap_or(VerificationMode::Verify),
+            offline: self.offline,
+            local_repository: self.local_repository,
+            artifact_sources: self.artifact_sources,
+            persistent_cache: self.persistent_cache,
+            negative_cache: self.negative_cache,
+            force_refresh: self.force_refresh,
+            credential_providers: self.credential_providers,
+            concurrency: self.concurrency.unwrap_or(8),
+            artifact_handlers: self.artifact_handlers,
+            url_fetcher,
+            pom_parser,
+            locks: Mutex::new(HashMap::new()),
+            downloads: Default::default(),
+        })
+    }
+}
@@ -0,0 +1,343 @@
+//! Maven's `settings.xml`, see <https://maven.apache.org/settings.html>.
+
+use crate::{Credentials, Repository, RepositoryPolicy};
+use std::path::PathBuf;
+#[cfg(feature = "default-impl")]
+use {
+    crate::{ChecksumPolicy, ResolverError, UpdatePolicy},
+    std::path::Path,
+};
+use std::sync::Arc;
+
+/// A `<mirror>`: a repository that serves requests for the repositories matched by `mirror_of`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    pub id: String,
+    pub url: String,
+    /// A comma-separated list of repository IDs, e.g. `*`, `external:*` or `*,!google`.
+    pub mirror_of: String,
+}
+
+impl Mirror {
+    /// Whether the mirror serves `repository`, same rules as Maven:
+    /// `*` matches everything, `external:*` everything but `localhost` and `file:` URLs,
+    /// and `!id` excludes a repository.
+    pub fn matches(&self, repository: &Repository) -> bool {
+        let patterns = self.mirror_of.split(',').map(str::trim);
+
+        let mut matched = false;
+        for pattern in patterns {
+            if let Some(excluded) = pattern.strip_prefix('!') {
+                if excluded == repository.id {
+                    return false;
+                }
+            } else if pattern == repository.id || pattern == "*" {
+                matched = true;
+            } else if pattern == "external:*" {
+                matched |= is_external(&repository.base_url);
+            }
+        }
+        matched
+    }
+}
+
+fn is_external(url: &str) -> bool {
+    let host = url
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(url)
+        .split(['/', ':'])
+        .next()
+        .unwrap_or_default();
+    !url.starts_with("file:") && host != "localhost" && host != "127.0.0.1"
+}
+
+/// A `<server>`, the credentials of the repository or mirror with the same ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Server {
+    pub id: String,
+    pub credentials: Credentials,
+}
+
+/// A `<profile>`, only the parts that matter for resolving.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub id: String,
+    pub active_by_default: bool,
+    pub repositories: Vec<SettingsRepository>,
+}
+
+/// A `<repository>` declared in a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingsRepository {
+    pub id: String,
+    pub url: String,
+    pub releases: RepositoryPolicy,
+    pub snapshots: RepositoryPolicy,
+}
+
+impl SettingsRepository {
+    pub fn to_repository(&self) -> Repository {
+        Repository::new(&self.id, &self.url)
+            .with_releases(self.releases)
+            .with_snapshots(self.snapshots)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settings {
+    pub local_repository: Option<PathBuf>,
+    pub offline: bool,
+    pub mirrors: Vec<Mirror>,
+    pub servers: Vec<Server>,
+    pub profiles: Vec<Profile>,
+    pub active_profiles: Vec<String>,
+}
+
+impl Settings {
+    /// `~/.m2/settings.xml`
+    pub fn user_default_path() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+        Some(PathBuf::from(home).join(".m2").join("settings.xml"))
+    }
+
+    /// The profiles listed in `<activeProfiles>`, or, if none of them exists,
+    /// the ones that are active by default, same as Maven.
+    pub fn active_profiles(&self) -> impl Iterator<Item = &Profile> {
+        let activated = self
+            .profiles
+            .iter()
+            .any(|profile| self.active_profiles.contains(&profile.id));
+        self.profiles.iter().filter(move |profile| match activated {
+            true => self.active_profiles.contains(&profile.id),
+            false => profile.active_by_default,
+        })
+    }
+
+    pub fn credentials(&self, id: &str) -> Option<&Credentials> {
+        self.servers
+            .iter()
+            .find(|server| server.id == id)
+            .map(|server| &server.credentials)
+    }
+
+    /// The first mirror that serves `repository`, preferring an exact ID match like Maven does.
+    pub fn mirror_of(&self, repository: &Repository) -> Option<&Mirror> {
+        self.mirrors
+            .iter()
+            .find(|mirror| mirror.mirror_of.trim() == repository.id)
+            .or_else(|| self.mirrors.iter().find(|mirror| mirror.matches(repository)))
+    }
+
+    /// The repositories to use: the ones from the active profiles followed by `repositories`,
+    /// with the mirrors and credentials applied.
+    ///
+    /// Repositories served by the same mirror are merged into one, which takes everything
+    /// but the policies from the first of them. Same as Maven, its releases and snapshots
+    /// policies are enabled if any of the repositories' are, checked for updates as often
+    /// as the most frequent of them, and with the strictest checksum policy, see
+    /// [`RepositoryPolicy::merge`].
+    pub fn repositories(&self, repositories: &[Arc<Repository>]) -> Vec<Arc<Repository>> {
+        let declared = self
+            .active_profiles()
+            .flat_map(|profile| profile.repositories.iter())
+            .map(|repository| repository.to_repository())
+            .chain(repositories.iter().map(|repository| repository.as_ref().clone()));
+
+        let mut result: Vec<Arc<Repository>> = vec![];
+        for repository in declared {
+            let mirror = self.mirror_of(&repository);
+            let mut repository = match mirror {
+                Some(mirror) => Repository {
+                    id: mirror.id.clone(),
+                    base_url: mirror.url.clone(),
+                    ..repository
+                },
+                None => repository,
+            };
+
+            if let Some(merged) = result.iter_mut().find(|r| r.id == repository.id) {
+                if mirror.is_some() {
+                    *merged = Arc::new(Repository {
+                        releases: merged.releases.merge(repository.releases),
+                        snapshots: merged.snapshots.merge(repository.snapshots),
+                        ..merged.as_ref().clone()
+                    });
+                }
+                continue;
+            }
+            if let Some(credentials) = self.credentials(&repository.id) {
+                repository.credentials = Some(credentials.clone());
+            }
+            result.push(Arc::new(repository));
+        }
+        result
+    }
+}
+
+#[cfg(feature = "default-impl")]
+fn children<'a, 'input>(
+    n: roxmltree::Node<'a, 'input>,
+    tag_name: &str,
+) -> Vec<roxmltree::Node<'a, 'input>> {
+    n.children()
+        .filter(|child| child.is_element() && child.has_tag_name(tag_name))
+        .collect()
+}
+
+#[cfg(feature = "default-impl")]
+fn child<'a, 'input>(
+    n: roxmltree::Node<'a, 'input>,
+    tag_name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    children(n, tag_name).into_iter().next()
+}
+
+/// The text of `<tag_name>`, with `${user.home}` and `${env.NAME}` expanded.
+#[cfg(feature = "default-impl")]
+fn text(n: roxmltree::Node, tag_name: &str) -> Option<String> {
+    let text = child(n, tag_name)?.text()?.trim();
+
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        let value = match name {
+            "user.home" => std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok(),
+            name => name.strip_prefix("env.").and_then(|name| std::env::var(name).ok()),
+        };
+        result += &rest[..start];
+        match value {
+            Some(value) => result += &value,
+            None => result += &rest[start..start + end + 1],
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result += rest;
+
+    Some(result)
+}
+
+/// Items of a list such as `<mirrors><mirror>...</mirror></mirrors>`.
+#[cfg(feature = "default-impl")]
+fn list<'a, 'input>(
+    n: roxmltree::Node<'a, 'input>,
+    list_name: &str,
+    tag_name: &str,
+) -> Vec<roxmltree::Node<'a, 'input>> {
+    children(n, list_name)
+        .into_iter()
+        .flat_map(|list| children(list, tag_name))
+        .collect()
+}
+
+#[cfg(feature = "default-impl")]
+fn parse_policy(n: Option<roxmltree::Node>) -> Result<RepositoryPolicy, ResolverError> {
+    let mut policy = RepositoryPolicy::default();
+    let Some(n) = n else {
+        return Ok(policy);
+    };
+
+    if let Some(enabled) = text(n, "enabled") {
+        policy.enabled = enabled != "false";
+    }
+    if let Some(update_policy) = text(n, "updatePolicy") {
+        policy.update_policy = UpdatePolicy::parse(&update_policy).ok_or_else(|| {
+            ResolverError::invalid_data(&format!("invalid updatePolicy: {}", update_policy))
+        })?;
+    }
+    if let Some(checksum_policy) = text(n, "checksumPolicy") {
+        policy.checksum_policy = ChecksumPolicy::parse(&checksum_policy).ok_or_else(|| {
+            ResolverError::invalid_data(&format!("invalid checksumPolicy: {}", checksum_policy))
+        })?;
+    }
+    Ok(policy)
+}
+
+#[cfg(feature = "default-impl")]
+impl Settings {
+    pub fn parse(input: &str) -> Result<Self, ResolverError> {
+        let doc = roxmltree::Document::parse(input)
+            .map_err(|e| ResolverError::invalid_data(&format!("invalid XML content, {}", e)))?;
+
+        let root = doc.root_element();
+        if !root.has_tag_name("settings") {
+            return Err(ResolverError::invalid_data(
+                "invalid XML content, no <settings> tag",
+            ));
+        }
+
+        let required = |n, tag_name: &str, what: &str| {
+            text(n, tag_name).ok_or_else(|| {
+                ResolverError::invalid_data(&format!("{} without <{}>", what, tag_name))
+            })
+        };
+
+        let mut settings = Settings {
+            local_repository: text(root, "localRepository").map(PathBuf::from),
+            offline: text(root, "offline").as_deref() == Some("true"),
+            ..Default::default()
+        };
+
+        for n in list(root, "mirrors", "mirror") {
+            settings.mirrors.push(Mirror {
+                id: required(n, "id", "<mirror>")?,
+                url: required(n, "url", "<mirror>")?,
+                mirror_of: required(n, "mirrorOf", "<mirror>")?,
+            });
+        }
+
+        for n in list(root, "servers", "server") {
+            let id = required(n, "id", "<server>")?;
+            // servers with keys or other configuration only aren't supported yet
+            if let Some(username) = text(n, "username") {
+                settings.servers.push(Server {
+                    id,
+                    credentials: Credentials::Basic {
+                        username,
+                        password: text(n, "password").unwrap_or_default(),
+                    },
+                });
+            }
+        }
+
+        for n in list(root, "profiles", "profile") {
+            let mut profile = Profile {
+                id: text(n, "id").unwrap_or_default(),
+                active_by_default: child(n, "activation")
+                    .and_then(|activation| text(activation, "activeByDefault"))
+                    .as_deref()
+                    == Some("true"),
+                repositories: vec![],
+            };
+
+            for repository in list(n, "repositories", "repository") {
+                profile.repositories.push(SettingsRepository {
+                    id: required(repository, "id", "<repository>")?,
+                    url: required(repository, "url", "<repository>")?,
+                    releases: parse_policy(child(repository, "releases"))?,
+                    snapshots: parse_policy(child(repository, "snapshots"))?,
+                });
+            }
+
+            settings.profiles.push(profile);
+        }
+
+        settings.active_profiles = children(root, "activeProfiles")
+            .into_iter()
+            .flat_map(|n| children(n, "activeProfile"))
+            .filter_map(|n| n.text())
+            .map(|text| text.trim().to_owned())
+            .collect();
+
+        Ok(settings)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ResolverError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ResolverError::io(path, e))?;
+        Self::parse(&text)
+    }
+}
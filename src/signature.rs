@@ -0,0 +1,574 @@
+//! Verification of detached OpenPGP signatures (`.asc` files).
+//!
+//! The packets are parsed and the signatures are checked by the `pgp` crate,
+//! this module decides which keys and signatures are acceptable: only v4 keys
+//! and signatures over binary documents are supported. Third-party
+//! certifications are not checked, the primary keys of the keyring supplied by
+//! the user are trusted as a whole, but their subkeys only with a valid binding
+//! signature. Revocations and expiration times are honored, signatures made
+//! with SHA-1 and signatures with critical subpackets we don't know are rejected.
+
+use crate::{ResolverError, UrlFetcher};
+use log::{debug, warn};
+use pgp::composed::{Deserializable, DetachedSignature, SignedPublicKey};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::packet::{RevocationCode, Signature, SignatureType, SubpacketType};
+use pgp::types::{KeyDetails, KeyVersion, Tag};
+use std::path::Path;
+
+/// What to do with artifact signatures.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignaturePolicy {
+    /// Every artifact must have a valid signature made by a key from the keyring.
+    Require,
+    /// Verify the signatures, but only log a warning if that fails.
+    Warn,
+    /// Don't fetch the signatures at all.
+    #[default]
+    Skip,
+}
+
+/// The fingerprint of an OpenPGP v4 key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub [u8; 20]);
+
+impl Fingerprint {
+    /// The 64-bit key ID, i.e. the last 8 bytes of the fingerprint.
+    pub fn key_id(&self) -> [u8; 8] {
+        self.0[12..].try_into().unwrap()
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode_upper(self.0))
+    }
+}
+
+impl std::str::FromStr for Fingerprint {
+    type Err = ResolverError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let bytes = hex::decode(s.trim_start_matches("0x"))
+            .map_err(|_| ResolverError::invalid_data(&format!("invalid key fingerprint {}", s)))?;
+        bytes
+            .try_into()
+            .map(Fingerprint)
+            .map_err(|_| ResolverError::invalid_data(&format!("invalid key fingerprint {}", s)))
+    }
+}
+
+/// The key packets a keyring is made of.
+enum KeyPacket {
+    Primary(pgp::packet::PublicKey),
+    Subkey(pgp::packet::PublicSubkey),
+}
+
+struct PublicKey {
+    fingerprint: Fingerprint,
+    packet: KeyPacket,
+    /// When the key was created, in seconds since the Unix epoch.
+    created: u64,
+    /// When the key expires, according to its latest self-signature.
+    expires: Option<u64>,
+    /// Since when the signatures made by the key are invalid, zero if it was
+    /// revoked because it was compromised.
+    revoked: Option<u64>,
+}
+
+impl PublicKey {
+    fn new(packet: KeyPacket) -> Option<Self> {
+        let details: &dyn KeyDetails = match &packet {
+            KeyPacket::Primary(key) => key,
+            KeyPacket::Subkey(key) => key,
+        };
+        if details.version() != KeyVersion::V4 {
+            debug!(
+                "skipping unsupported {:?} key {}",
+                details.version(),
+                details.fingerprint()
+            );
+            return None;
+        }
+        Some(PublicKey {
+            fingerprint: Fingerprint(details.fingerprint().as_bytes().try_into().ok()?),
+            created: details.created_at().as_secs().into(),
+            packet,
+            expires: None,
+            revoked: None,
+        })
+    }
+
+    /// Checks that the key could make a signature at `time`.
+    fn check_valid_at(&self, time: u64) -> Result<(), ResolverError> {
+        let problem = if time < self.created {
+            "was created after the signature"
+        } else if self.expires.is_some_and(|expires| time >= expires) {
+            "had expired when the signature was made"
+        } else if self.revoked.is_some_and(|revoked| time >= revoked) {
+            "is revoked"
+        } else {
+            return Ok(());
+        };
+        Err(ResolverError::signature_invalid(&format!(
+            "key {} {}",
+            self.fingerprint, problem
+        )))
+    }
+
+    fn revoke(&mut self, revocation: &Signature) {
+        let since = match revocation.revocation_reason_code() {
+            // superseded or retired, the signatures made before remain valid
+            Some(RevocationCode::KeySuperseded | RevocationCode::KeyRetired) => {
+                created(revocation).unwrap_or(0)
+            }
+            _ => 0,
+        };
+        self.revoked = earliest(self.revoked, Some(since));
+    }
+
+    fn check_signature(&self, signature: &Signature, data: &Data) -> Result<(), ResolverError> {
+        let verified = match (&self.packet, data) {
+            (KeyPacket::Primary(key), Data::Bytes(data)) => signature.verify(key, *data),
+            (KeyPacket::Subkey(key), Data::Bytes(data)) => signature.verify(key, *data),
+            (packet, Data::File(path)) => {
+                let file = std::fs::File::open(path).map_err(|e| ResolverError::io(path, e))?;
+                let file = std::io::BufReader::new(file);
+                match packet {
+                    KeyPacket::Primary(key) => signature.verify(key, file),
+                    KeyPacket::Subkey(key) => signature.verify(key, file),
+                }
+            }
+        };
+        verified.map_err(|e| {
+            ResolverError::signature_invalid(&format!(
+                "bad signature made by {}: {}",
+                self.fingerprint, e
+            ))
+        })
+    }
+}
+
+/// Returns the primary key of `certificate` and the subkeys bound to it for
+/// signing, with their expiration and revocation times.
+fn keys_of(certificate: SignedPublicKey) -> Vec<PublicKey> {
+    let primary_packet = &certificate.primary_key;
+    let Some(mut primary) = PublicKey::new(KeyPacket::Primary(primary_packet.clone())) else {
+        return vec![];
+    };
+
+    let details = &certificate.details;
+    for revocation in &details.revocation_signatures {
+        if understood(revocation).is_ok() && revocation.verify_key(primary_packet).is_ok() {
+            primary.revoke(revocation);
+        }
+    }
+    let direct_signatures = details.direct_signatures.iter().filter(|signature| {
+        understood(signature).is_ok() && signature.verify_key(primary_packet).is_ok()
+    });
+    let certifications = details.users.iter().flat_map(|user| {
+        user.signatures.iter().filter(|signature| {
+            signature.is_certification()
+                && understood(signature).is_ok()
+                && signature
+                    .verify_certification(primary_packet, Tag::UserId, &user.id)
+                    .is_ok()
+        })
+    });
+    if let Some(latest) = direct_signatures
+        .chain(certifications)
+        .max_by_key(|signature| created(signature))
+    {
+        primary.expires = key_expiration(latest, primary.created);
+    }
+
+    let mut keys = vec![];
+    for subkey in &certificate.public_subkeys {
+        let Some(mut key) = PublicKey::new(KeyPacket::Subkey(subkey.key.clone())) else {
+            continue;
+        };
+        let binds = |signature: &&Signature| {
+            understood(signature).is_ok()
+                && signature
+                    .verify_subkey_binding(primary_packet, &subkey.key)
+                    .is_ok()
+        };
+
+        let binding = subkey
+            .signatures
+            .iter()
+            .filter(|signature| signature.typ() == Some(SignatureType::SubkeyBinding))
+            .filter(binds)
+            .max_by_key(|signature| created(signature));
+        let Some(binding) = binding else {
+            debug!(
+                "skipping subkey {} without a valid binding signature",
+                key.fingerprint
+            );
+            continue;
+        };
+        if !binding.key_flags().sign() {
+            // not a signing subkey
+            continue;
+        }
+        // signing subkeys must sign the binding back, otherwise anyone
+        // could claim the subkey of someone else
+        let back_signed = binding.embedded_signature().is_some_and(|signature| {
+            understood(signature).is_ok()
+                && signature
+                    .verify_primary_key_binding(&subkey.key, primary_packet)
+                    .is_ok()
+        });
+        if !back_signed {
+            debug!(
+                "skipping subkey {} without a valid back signature",
+                key.fingerprint
+            );
+            continue;
+        }
+
+        key.expires = key_expiration(binding, key.created);
+        for revocation in subkey
+            .signatures
+            .iter()
+            .filter(|signature| signature.typ() == Some(SignatureType::SubkeyRevocation))
+            .filter(binds)
+        {
+            key.revoke(revocation);
+        }
+
+        // a subkey is only valid as long as its primary key
+        key.expires = earliest(key.expires, primary.expires);
+        key.revoked = earliest(key.revoked, primary.revoked);
+        keys.push(key);
+    }
+
+    keys.insert(0, primary);
+    keys
+}
+
+/// Rejects the signatures with critical subpackets we don't know what to do
+/// with, as their signer asked: the `pgp` crate only rejects the subpacket
+/// types that aren't assigned at all.
+fn understood(signature: &Signature) -> Result<(), ResolverError> {
+    let Some(config) = signature.config() else {
+        return Err(ResolverError::signature_invalid(&format!(
+            "unsupported signature version {:?}",
+            signature.version()
+        )));
+    };
+    let unknown = config.hashed_subpackets().find(|subpacket| {
+        subpacket.is_critical
+            && !matches!(
+                subpacket.typ(),
+                SubpacketType::SignatureCreationTime
+                    | SubpacketType::SignatureExpirationTime
+                    | SubpacketType::KeyExpirationTime
+                    | SubpacketType::ExportableCertification
+                    | SubpacketType::Revocable
+                    | SubpacketType::PreferredSymmetricAlgorithms
+                    | SubpacketType::PreferredHashAlgorithms
+                    | SubpacketType::PreferredCompressionAlgorithms
+                    | SubpacketType::PreferredAead
+                    | SubpacketType::PreferredEncryptionModes
+                    | SubpacketType::KeyServerPreferences
+                    | SubpacketType::PrimaryUserId
+                    | SubpacketType::IssuerKeyId
+                    | SubpacketType::IssuerFingerprint
+                    | SubpacketType::KeyFlags
+                    | SubpacketType::Features
+                    | SubpacketType::RevocationReason
+                    | SubpacketType::EmbeddedSignature
+            )
+    });
+    match unknown {
+        Some(subpacket) => Err(ResolverError::signature_invalid(&format!(
+            "unknown critical subpacket {:?}",
+            subpacket.typ()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// When the signature was made, in seconds since the Unix epoch.
+fn created(signature: &Signature) -> Option<u64> {
+    signature.created().map(|created| created.as_secs().into())
+}
+
+/// When the key a self-signature is over expires, None for never.
+fn key_expiration(signature: &Signature, key_created: u64) -> Option<u64> {
+    signature
+        .key_expiration_time()
+        .map(|duration| u64::from(duration.as_secs()))
+        .filter(|seconds| *seconds != 0)
+        .map(|seconds| key_created.saturating_add(seconds))
+}
+
+/// The earliest of two optional deadlines, None meaning never.
+fn earliest(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Parses all the compositions of `data`, either binary or made of one or
+/// more ASCII-armored blocks. Those that can't be parsed are skipped.
+fn parse_all<T: Deserializable>(data: &[u8], what: &str) -> Result<Vec<T>, ResolverError> {
+    const BEGIN: &[u8] = b"-----BEGIN PGP ";
+
+    let invalid = |e: pgp::errors::Error| {
+        ResolverError::invalid_data(&format!("malformed OpenPGP {}: {}", what, e))
+    };
+
+    let starts: Vec<usize> = (0..data.len())
+        .filter(|&i| data[i..].starts_with(BEGIN))
+        .collect();
+    let parsed: Vec<_> = if starts.is_empty() {
+        T::from_bytes_many(data).map_err(invalid)?.collect()
+    } else {
+        let mut parsed = vec![];
+        for (i, start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(data.len());
+            let (items, _) = T::from_armor_many_buf(&data[*start..end]).map_err(invalid)?;
+            parsed.extend(items);
+        }
+        parsed
+    };
+
+    Ok(parsed
+        .into_iter()
+        .filter_map(|item| {
+            item.map_err(|e| debug!("skipping an unsupported OpenPGP {}: {}", what, e))
+                .ok()
+        })
+        .collect())
+}
+
+/// A set of trusted public keys.
+#[derive(Default)]
+pub struct Keyring {
+    keys: Vec<PublicKey>,
+}
+
+impl Keyring {
+    /// Parses a keyring, either ASCII-armored or binary. Several armored
+    /// blocks can be concatenated in one file.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ResolverError> {
+        let certificates: Vec<SignedPublicKey> = parse_all(data, "key")?;
+        Ok(Keyring {
+            keys: certificates.into_iter().flat_map(keys_of).collect(),
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ResolverError> {
+        let data = std::fs::read(path).map_err(|e| ResolverError::io(path, e))?;
+        Self::from_bytes(&data)
+    }
+
+    /// Adds all keys from `other` to this keyring.
+    pub fn extend(&mut self, other: Keyring) {
+        self.keys.extend(other.keys);
+    }
+
+    pub fn fingerprints(&self) -> impl Iterator<Item = &Fingerprint> {
+        self.keys.iter().map(|key| &key.fingerprint)
+    }
+
+    /// Checks a detached signature of `data` and returns the fingerprint of
+    /// the key that made it.
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<Fingerprint, ResolverError> {
+        self.verify_data(&Data::Bytes(data), signature)
+    }
+
+    /// Same as [`Keyring::verify`] for a file, which is read in chunks.
+    pub fn verify_file(&self, path: &Path, signature: &[u8]) -> Result<Fingerprint, ResolverError> {
+        self.verify_data(&Data::File(path), signature)
+    }
+
+    fn verify_data(&self, data: &Data, signature: &[u8]) -> Result<Fingerprint, ResolverError> {
+        let mut last_error = ResolverError::signature_invalid("no signature packets found");
+
+        let signatures: Vec<DetachedSignature> = parse_all(signature, "signature")?;
+        for signature in signatures {
+            match self.verify_signature(data, &signature.signature) {
+                Ok(fingerprint) => return Ok(fingerprint),
+                Err(e) => last_error = e,
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn verify_signature(
+        &self,
+        data: &Data,
+        signature: &Signature,
+    ) -> Result<Fingerprint, ResolverError> {
+        if signature.typ() != Some(SignatureType::Binary) {
+            return Err(ResolverError::signature_invalid(&format!(
+                "unsupported signature type {:?}",
+                signature.typ()
+            )));
+        }
+
+        let key = self
+            .keys
+            .iter()
+            .find(|key| issued_by(signature, &key.fingerprint))
+            .ok_or_else(|| ResolverError::signature_untrusted(&issuer(signature)))?;
+
+        // SHA-1 is broken, a signature could be made to match other data
+        let weak = match signature.hash_alg() {
+            Some(HashAlgorithm::Sha1) => Some("SHA-1"),
+            Some(HashAlgorithm::Md5) => Some("MD5"),
+            Some(HashAlgorithm::Ripemd160) => Some("RIPEMD-160"),
+            _ => None,
+        };
+        if let Some(weak) = weak {
+            return Err(ResolverError::signature_invalid(&format!(
+                "signatures made with {} are not accepted",
+                weak
+            )));
+        }
+        understood(signature)?;
+
+        let created = created(signature)
+            .ok_or_else(|| ResolverError::signature_invalid("signature without a creation time"))?;
+        let now = now();
+        if created > now {
+            return Err(ResolverError::signature_invalid(
+                "signature created in the future",
+            ));
+        }
+        let expires = signature
+            .signature_expiration_time()
+            .map(|duration| u64::from(duration.as_secs()))
+            .filter(|seconds| *seconds != 0)
+            .map(|seconds| created.saturating_add(seconds));
+        if expires.is_some_and(|expires| expires <= now) {
+            return Err(ResolverError::signature_invalid("signature expired"));
+        }
+
+        key.check_valid_at(created)?;
+        key.check_signature(signature, data)?;
+
+        Ok(key.fingerprint.clone())
+    }
+}
+
+fn issued_by(signature: &Signature, fingerprint: &Fingerprint) -> bool {
+    let fingerprints = signature.issuer_fingerprint();
+    if !fingerprints.is_empty() {
+        return fingerprints
+            .iter()
+            .any(|issuer| issuer.as_bytes() == fingerprint.0);
+    }
+    signature
+        .issuer_key_id()
+        .iter()
+        .any(|key_id| key_id.as_ref() == fingerprint.key_id())
+}
+
+fn issuer(signature: &Signature) -> String {
+    if let Some(fingerprint) = signature.issuer_fingerprint().first() {
+        hex::encode_upper(fingerprint.as_bytes())
+    } else if let Some(key_id) = signature.issuer_key_id().first() {
+        hex::encode_upper(key_id)
+    } else {
+        "an unknown key".to_owned()
+    }
+}
+
+/// The signed data, in memory or in a file.
+enum Data<'a> {
+    Bytes(&'a [u8]),
+    File(&'a Path),
+}
+
+/// Checks the signature of `data` downloaded from `url` using the `.asc` file next to it.
+///
+/// Returns the fingerprint of the signer if the signature was verified.
+pub fn verify(
+    url_fetcher: &dyn UrlFetcher,
+    keyring: &Keyring,
+    policy: SignaturePolicy,
+    url: &str,
+    data: &[u8],
+) -> Result<Option<Fingerprint>, ResolverError> {
+    verify_with(url_fetcher, policy, url, |signature| {
+        keyring.verify(data, signature)
+    })
+}
+
+/// Same as [`verify`] for a downloaded file.
+pub fn verify_file(
+    url_fetcher: &dyn UrlFetcher,
+    keyring: &Keyring,
+    policy: SignaturePolicy,
+    url: &str,
+    path: &Path,
+) -> Result<Option<Fingerprint>, ResolverError> {
+    verify_with(url_fetcher, policy, url, |signature| {
+        keyring.verify_file(path, signature)
+    })
+}
+
+fn verify_with(
+    url_fetcher: &dyn UrlFetcher,
+    policy: SignaturePolicy,
+    url: &str,
+    verify: impl FnOnce(&[u8]) -> Result<Fingerprint, ResolverError>,
+) -> Result<Option<Fingerprint>, ResolverError> {
+    if policy == SignaturePolicy::Skip {
+        return Ok(None);
+    }
+
+    let signature_url = format!("{}.asc", url);
+    check(policy, url, url_fetcher.fetch_bytes(&signature_url), verify)
+}
+
+/// Verifies `url` with the `.asc` signature `fetched` next to it.
+pub(crate) fn check(
+    policy: SignaturePolicy,
+    url: &str,
+    fetched: Result<bytes::Bytes, ResolverError>,
+    verify: impl FnOnce(&[u8]) -> Result<Fingerprint, ResolverError>,
+) -> Result<Option<Fingerprint>, ResolverError> {
+    if policy == SignaturePolicy::Skip {
+        return Ok(None);
+    }
+
+    let result = fetched
+        .map_err(|_| ResolverError::signature_missing(url))
+        .and_then(|signature| verify(&signature))
+        .map_err(|mut e| {
+            e.msg = format!("{}: {}", crate::credentials::redact(url), e.msg);
+            e
+        });
+
+    match (result, policy) {
+        (Ok(fingerprint), _) => {
+            debug!(
+                "{} is signed by {}",
+                crate::credentials::redact(url),
+                fingerprint
+            );
+            Ok(Some(fingerprint))
+        }
+        (Err(e), SignaturePolicy::Warn) => {
+            warn!("{}", e.msg);
+            Ok(None)
+        }
+        (Err(e), _) => Err(e),
+    }
+}
@@ -0,0 +1,554 @@
+//! Gradle-compatible dependency verification metadata,
+//! i.e. `gradle/verification-metadata.xml`.
+//!
+//! See <https://docs.gradle.org/current/userguide/dependency_verification.html>.
+
+use crate::checksum::{ChecksumAlgorithm, Digests};
+use crate::{Artifact, ResolverError};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The `origin` we put on the checksums we write.
+const ORIGIN: &str = "Generated by maven-rs";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationMode {
+    /// Fail if a downloaded file doesn't match the metadata.
+    Verify,
+    /// Don't verify anything, add (or update) the checksums of every
+    /// downloaded file instead. Same as Gradle's `--write-verification-metadata`.
+    Record(ChecksumAlgorithm),
+}
+
+/// A `<trusted-key>` entry: a key trusted to sign the matching artifacts.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrustedKey {
+    /// A long key ID, i.e. 16 hex digits, or a fingerprint.
+    pub id: String,
+    pub group: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub file: Option<String>,
+    /// Whether `group`, `name`, `version` and `file` are regular expressions.
+    pub regex: bool,
+}
+
+impl TrustedKey {
+    fn matches_key(&self, fingerprint: &str) -> bool {
+        is_key(&self.id, fingerprint)
+    }
+
+    fn matches(&self, group: &str, name: &str, version: &str, file_name: &str) -> bool {
+        [
+            (&self.group, group),
+            (&self.name, name),
+            (&self.version, version),
+            (&self.file, file_name),
+        ]
+        .into_iter()
+        .all(|(pattern, value)| matches_pattern(pattern, self.regex, value))
+    }
+}
+
+/// A `<trust>` entry of `<trusted-artifacts>`: the matching files aren't verified at all.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrustedArtifact {
+    pub group: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub file: Option<String>,
+    /// Whether `group`, `name`, `version` and `file` are regular expressions.
+    pub regex: bool,
+    pub reason: Option<String>,
+}
+
+impl TrustedArtifact {
+    fn matches(&self, component: &ComponentId, file_name: &str) -> bool {
+        [
+            (&self.group, component.group.as_str()),
+            (&self.name, component.name.as_str()),
+            (&self.version, component.version.as_str()),
+            (&self.file, file_name),
+        ]
+        .into_iter()
+        .all(|(pattern, value)| matches_pattern(pattern, self.regex, value))
+    }
+}
+
+/// An `<ignored-key>` entry: signatures of the key are never trusted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IgnoredKey {
+    /// A long key ID, i.e. 16 hex digits, or a fingerprint.
+    pub id: String,
+    pub reason: Option<String>,
+}
+
+fn matches_pattern(pattern: &Option<String>, regex: bool, value: &str) -> bool {
+    match pattern {
+        None => true,
+        Some(pattern) if regex => regex::Regex::new(&format!("^(?:{})$", pattern))
+            .map(|re| re.is_match(value))
+            .unwrap_or(false),
+        Some(pattern) => pattern == value,
+    }
+}
+
+/// Whether `id`, a long (16 hex digits) key ID or a fingerprint, is the key with `fingerprint`.
+fn is_key(id: &str, fingerprint: &str) -> bool {
+    let id = id.to_ascii_uppercase();
+    let fingerprint = fingerprint.to_ascii_uppercase();
+    if !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return false;
+    }
+    match id.len() {
+        16 => fingerprint.ends_with(&id),
+        40 => fingerprint == id,
+        _ => false,
+    }
+}
+
+/// Expected checksums and signing keys of a single file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArtifactVerification {
+    /// Accepted values for each algorithm, the first one is the primary `value`,
+    /// the rest come from `<also-trust>`.
+    pub checksums: BTreeMap<ChecksumAlgorithm, Vec<String>>,
+    /// Keys trusted to sign this particular file.
+    pub pgp_keys: Vec<String>,
+    /// Where each checksum comes from, e.g. `Generated by Gradle`.
+    pub origins: BTreeMap<ChecksumAlgorithm, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ComponentId {
+    pub group: String,
+    pub name: String,
+    pub version: String,
+}
+
+impl ComponentId {
+    pub fn of(id: &Artifact) -> Option<Self> {
+        Some(ComponentId {
+            group: id.group_id.clone()?,
+            name: id.artifact_id.clone()?,
+            version: id.version_cleaned()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationMetadata {
+    pub verify_metadata: bool,
+    pub verify_signatures: bool,
+    pub trusted_artifacts: Vec<TrustedArtifact>,
+    pub ignored_keys: Vec<IgnoredKey>,
+    pub trusted_keys: Vec<TrustedKey>,
+    /// The other elements of `<configuration>`, e.g. `<key-servers>`, as they were
+    /// parsed, so that they're written back unchanged.
+    pub other_configuration: Vec<String>,
+    /// Per-component, per-file entries.
+    pub components: BTreeMap<ComponentId, BTreeMap<String, ArtifactVerification>>,
+}
+
+impl Default for VerificationMetadata {
+    fn default() -> Self {
+        VerificationMetadata {
+            verify_metadata: true,
+            verify_signatures: false,
+            trusted_artifacts: vec![],
+            ignored_keys: vec![],
+            trusted_keys: vec![],
+            other_configuration: vec![],
+            components: BTreeMap::new(),
+        }
+    }
+}
+
+impl VerificationMetadata {
+    pub fn get(&self, id: &Artifact, file_name: &str) -> Option<&ArtifactVerification> {
+        self.components.get(&ComponentId::of(id)?)?.get(file_name)
+    }
+
+    /// Checks a downloaded file. `signer` is the fingerprint of the key that
+    /// signed the file, if its signature was verified.
+    pub fn verify(
+        &self,
+        id: &Artifact,
+        file_name: &str,
+        data: &[u8],
+        signer: Option<&str>,
+    ) -> Result<(), ResolverError> {
+        self.verify_digests(id, file_name, &Digests::of(data), signer)
+    }
+
+    /// Same as [`VerificationMetadata::verify`] with the digests of the file.
+    pub fn verify_digests(
+        &self,
+        id: &Artifact,
+        file_name: &str,
+        digests: &Digests,
+        signer: Option<&str>,
+    ) -> Result<(), ResolverError> {
+        let component = ComponentId::of(id)
+            .ok_or_else(|| ResolverError::invalid_data(&format!("incomplete coordinates {}", id)))?;
+        let entry = self.get(id, file_name);
+
+        if self
+            .trusted_artifacts
+            .iter()
+            .any(|trusted| trusted.matches(&component, file_name))
+        {
+            return Ok(());
+        }
+
+        // a trusted signature is enough, no checksums required
+        let signer = signer.filter(|signer| !self.ignored_keys.iter().any(|key| is_key(&key.id, signer)));
+        if let (true, Some(signer)) = (self.verify_signatures, signer) {
+            let trusted_globally = self.trusted_keys.iter().any(|key| {
+                key.matches_key(signer)
+                    && key.matches(&component.group, &component.name, &component.version, file_name)
+            });
+            let trusted_locally =
+                entry.is_some_and(|entry| entry.pgp_keys.iter().any(|key| is_key(key, signer)));
+            if trusted_globally || trusted_locally {
+                return Ok(());
+            }
+        }
+
+        let Some(entry) = entry.filter(|entry| !entry.checksums.is_empty()) else {
+            if !self.verify_metadata && file_name.ends_with(".pom") {
+                return Ok(());
+            }
+            return Err(ResolverError::verification_metadata_missing(id, file_name));
+        };
+
+        for (algorithm, values) in &entry.checksums {
+            let actual = digests.get(*algorithm);
+            if !values.iter().any(|value| value.eq_ignore_ascii_case(actual)) {
+                return Err(ResolverError::checksum_mismatch(
+                    file_name,
+                    algorithm,
+                    &values.join(" or "),
+                    actual,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds or replaces the checksum of a downloaded file and records its signer.
+    pub fn record(
+        &mut self,
+        id: &Artifact,
+        file_name: &str,
+        data: &[u8],
+        algorithm: ChecksumAlgorithm,
+        signer: Option<&str>,
+    ) {
+        self.record_digests(id, file_name, &Digests::of(data), algorithm, signer)
+    }
+
+    /// Same as [`VerificationMetadata::record`] with the digests of the file.
+    pub fn record_digests(
+        &mut self,
+        id: &Artifact,
+        file_name: &str,
+        digests: &Digests,
+        algorithm: ChecksumAlgorithm,
+        signer: Option<&str>,
+    ) {
+        let Some(component) = ComponentId::of(id) else {
+            return;
+        };
+
+        let entry = self
+            .components
+            .entry(component)
+            .or_default()
+            .entry(file_name.to_owned())
+            .or_default();
+
+        // a checksum that's already trusted keeps its origin and the other trusted values
+        let value = digests.get(algorithm);
+        let unchanged = entry
+            .checksums
+            .get(&algorithm)
+            .is_some_and(|values| values.iter().any(|trusted| trusted.eq_ignore_ascii_case(value)));
+        if !unchanged {
+            entry.checksums.insert(algorithm, vec![value.to_owned()]);
+            entry.origins.insert(algorithm, ORIGIN.to_owned());
+        }
+
+        if let Some(signer) = signer {
+            if !entry.pgp_keys.iter().any(|key| key.eq_ignore_ascii_case(signer)) {
+                entry.pgp_keys.push(signer.to_owned());
+            }
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        }
+
+        let mut xml = String::new();
+        xml += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+        xml += "<verification-metadata xmlns=\"https://schema.gradle.org/dependency-verification\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"https://schema.gradle.org/dependency-verification https://schema.gradle.org/dependency-verification/dependency-verification-1.3.xsd\">\n";
+        xml += "   <configuration>\n";
+        xml += &format!("      <verify-metadata>{}</verify-metadata>\n", self.verify_metadata);
+        xml += &format!("      <verify-signatures>{}</verify-signatures>\n", self.verify_signatures);
+        for other in &self.other_configuration {
+            xml += &format!("      {}\n", other);
+        }
+        let coordinates = |group: &Option<String>, name: &Option<String>, version: &Option<String>, file: &Option<String>, regex: bool| {
+            let mut xml = String::new();
+            for (attr, value) in [("group", group), ("name", name), ("version", version), ("file", file)] {
+                if let Some(value) = value {
+                    xml += &format!(" {}=\"{}\"", attr, escape(value));
+                }
+            }
+            if regex {
+                xml += " regex=\"true\"";
+            }
+            xml
+        };
+        let reason = |reason: &Option<String>| match reason {
+            Some(reason) => format!(" reason=\"{}\"", escape(reason)),
+            None => String::new(),
+        };
+        if !self.trusted_artifacts.is_empty() {
+            xml += "      <trusted-artifacts>\n";
+            for trusted in &self.trusted_artifacts {
+                xml += &format!(
+                    "         <trust{}{}/>\n",
+                    coordinates(&trusted.group, &trusted.name, &trusted.version, &trusted.file, trusted.regex),
+                    reason(&trusted.reason)
+                );
+            }
+            xml += "      </trusted-artifacts>\n";
+        }
+        if !self.ignored_keys.is_empty() {
+            xml += "      <ignored-keys>\n";
+            for key in &self.ignored_keys {
+                xml += &format!("         <ignored-key id=\"{}\"{}/>\n", escape(&key.id), reason(&key.reason));
+            }
+            xml += "      </ignored-keys>\n";
+        }
+        if !self.trusted_keys.is_empty() {
+            xml += "      <trusted-keys>\n";
+            for key in &self.trusted_keys {
+                xml += &format!(
+                    "         <trusted-key id=\"{}\"{}/>\n",
+                    escape(&key.id),
+                    coordinates(&key.group, &key.name, &key.version, &key.file, key.regex)
+                );
+            }
+            xml += "      </trusted-keys>\n";
+        }
+        xml += "   </configuration>\n";
+        xml += "   <components>\n";
+        for (component, artifacts) in &self.components {
+            xml += &format!(
+                "      <component group=\"{}\" name=\"{}\" version=\"{}\">\n",
+                escape(&component.group),
+                escape(&component.name),
+                escape(&component.version)
+            );
+            for (file_name, entry) in artifacts {
+                xml += &format!("         <artifact name=\"{}\">\n", escape(file_name));
+                // weakest first, same as Gradle
+                for (algorithm, values) in entry.checksums.iter().rev() {
+                    let Some((value, also_trust)) = values.split_first() else {
+                        continue;
+                    };
+                    let tag = algorithm.extension();
+                    let origin = entry.origins.get(algorithm).map_or(ORIGIN, String::as_str);
+                    xml += &format!(
+                        "            <{} value=\"{}\" origin=\"{}\"",
+                        tag,
+                        escape(value),
+                        escape(origin)
+                    );
+                    if also_trust.is_empty() {
+                        xml += "/>\n";
+                    } else {
+                        xml += ">\n";
+                        for value in also_trust {
+                            xml += &format!("               <also-trust value=\"{}\"/>\n", escape(value));
+                        }
+                        xml += &format!("            </{}>\n", tag);
+                    }
+                }
+                for key in &entry.pgp_keys {
+                    xml += &format!("            <pgp value=\"{}\"/>\n", escape(key));
+                }
+                xml += "         </artifact>\n";
+            }
+            xml += "      </component>\n";
+        }
+        xml += "   </components>\n";
+        xml += "</verification-metadata>\n";
+        xml
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), ResolverError> {
+        std::fs::write(path, self.to_xml()).map_err(|e| ResolverError::io(path, e))
+    }
+}
+
+#[cfg(feature = "default-impl")]
+fn children<'a, 'input>(
+    n: roxmltree::Node<'a, 'input>,
+    tag_name: &str,
+) -> Vec<roxmltree::Node<'a, 'input>> {
+    n.children()
+        .filter(|child| child.is_element() && child.has_tag_name(tag_name))
+        .collect()
+}
+
+#[cfg(feature = "default-impl")]
+fn child<'a, 'input>(
+    n: roxmltree::Node<'a, 'input>,
+    tag_name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    children(n, tag_name).into_iter().next()
+}
+
+#[cfg(feature = "default-impl")]
+fn attr(n: roxmltree::Node, name: &str) -> Option<String> {
+    n.attribute(name).map(str::to_owned)
+}
+
+#[cfg(feature = "default-impl")]
+impl VerificationMetadata {
+    pub fn parse(input: &str) -> Result<Self, ResolverError> {
+        let doc = roxmltree::Document::parse(input)
+            .map_err(|e| ResolverError::invalid_data(&format!("invalid XML content, {}", e)))?;
+
+        let root = doc.root_element();
+        if !root.has_tag_name("verification-metadata") {
+            return Err(ResolverError::invalid_data(
+                "invalid XML content, no <verification-metadata> tag",
+            ));
+        }
+
+        let mut metadata = VerificationMetadata::default();
+
+        if let Some(configuration) = child(root, "configuration") {
+            let flag = |tag_name, default| {
+                child(configuration, tag_name)
+                    .and_then(|n| n.text())
+                    .map(|text| text.trim() == "true")
+                    .unwrap_or(default)
+            };
+            metadata.verify_metadata = flag("verify-metadata", true);
+            metadata.verify_signatures = flag("verify-signatures", false);
+
+            for trusted_artifacts in children(configuration, "trusted-artifacts") {
+                for trust in children(trusted_artifacts, "trust") {
+                    metadata.trusted_artifacts.push(TrustedArtifact {
+                        group: attr(trust, "group"),
+                        name: attr(trust, "name"),
+                        version: attr(trust, "version"),
+                        file: attr(trust, "file"),
+                        regex: trust.attribute("regex") == Some("true"),
+                        reason: attr(trust, "reason"),
+                    });
+                }
+            }
+
+            for ignored_keys in children(configuration, "ignored-keys") {
+                for key in children(ignored_keys, "ignored-key") {
+                    metadata.ignored_keys.push(IgnoredKey {
+                        id: attr(key, "id").unwrap_or_default(),
+                        reason: attr(key, "reason"),
+                    });
+                }
+            }
+
+            const KNOWN: [&str; 5] = [
+                "verify-metadata",
+                "verify-signatures",
+                "trusted-artifacts",
+                "ignored-keys",
+                "trusted-keys",
+            ];
+            metadata.other_configuration = configuration
+                .children()
+                .filter(|n| n.is_element() && !KNOWN.contains(&n.tag_name().name()))
+                .map(|n| input[n.range()].to_owned())
+                .collect();
+
+            for trusted_keys in children(configuration, "trusted-keys") {
+                for key in children(trusted_keys, "trusted-key") {
+                    let parse_key = |n: roxmltree::Node<'_, '_>, id: &str| TrustedKey {
+                        id: id.to_owned(),
+                        group: attr(n, "group"),
+                        name: attr(n, "name"),
+                        version: attr(n, "version"),
+                        file: attr(n, "file"),
+                        regex: n.attribute("regex") == Some("true"),
+                    };
+
+                    let id = key.attribute("id").unwrap_or_default();
+                    let trusting = children(key, "trusting");
+                    if trusting.is_empty() {
+                        metadata.trusted_keys.push(parse_key(key, id));
+                    } else {
+                        metadata
+                            .trusted_keys
+                            .extend(trusting.into_iter().map(|n| parse_key(n, id)));
+                    }
+                }
+            }
+        }
+
+        for components in children(root, "components") {
+            for component in children(components, "component") {
+                let id = ComponentId {
+                    group: attr(component, "group").unwrap_or_default(),
+                    name: attr(component, "name").unwrap_or_default(),
+                    version: attr(component, "version").unwrap_or_default(),
+                };
+
+                let artifacts = metadata.components.entry(id).or_default();
+                for artifact in children(component, "artifact") {
+                    let mut entry = ArtifactVerification::default();
+
+                    for algorithm in ChecksumAlgorithm::ALL {
+                        for n in children(artifact, algorithm.extension()) {
+                            if let Some(origin) = attr(n, "origin") {
+                                entry.origins.entry(algorithm).or_insert(origin);
+                            }
+                            let values = entry.checksums.entry(algorithm).or_default();
+                            values.extend(attr(n, "value"));
+                            values.extend(
+                                children(n, "also-trust")
+                                    .into_iter()
+                                    .filter_map(|n| attr(n, "value")),
+                            );
+                        }
+                    }
+
+                    entry.pgp_keys = children(artifact, "pgp")
+                        .into_iter()
+                        .filter_map(|n| attr(n, "value"))
+                        .collect();
+
+                    artifacts.insert(attr(artifact, "name").unwrap_or_default(), entry);
+                }
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ResolverError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ResolverError::io(path, e))?;
+        Self::parse(&text)
+    }
+}